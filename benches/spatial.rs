@@ -0,0 +1,174 @@
+//! How much the spatial index saves over going through everything, on a
+//! level far bigger than the shipped ones, with a crowd of ghosts.
+//! Run with `cargo bench --bench spatial`. Every timing rebuilds the grid
+//! first, the way the game does every tick, and checks both ways agree.
+
+use std::{
+    hint::black_box,
+    time::{Duration, Instant},
+};
+
+use bevy::math::{Quat, Vec2, Vec3, Vec3Swizzles};
+use jamprogamer::{move_and_slide, Collider, Obstacle, SpatialGrid, CHARACTER_RADIUS};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+/// Rooms per side of the level.
+const ROOMS: i32 = 40;
+const ROOM_SIZE: f32 = 8.0;
+const CHARACTERS: usize = 500;
+const ITERATIONS: u32 = 20;
+
+fn main() {
+    let mut rng = StdRng::seed_from_u64(0);
+    let walls = walls(&mut rng);
+    let characters: Vec<Vec3> = (0..CHARACTERS)
+        .map(|_| {
+            let size = ROOMS as f32 * ROOM_SIZE;
+            Vec3::new(rng.gen_range(0.0..size), 0.0, rng.gen_range(0.0..size))
+        })
+        .collect();
+    let steps: Vec<Vec2> = (0..CHARACTERS)
+        .map(|_| Vec2::from_angle(rng.gen_range(0.0..std::f32::consts::TAU)) * 0.1)
+        .collect();
+    println!("{} walls, {} characters", walls.len(), characters.len());
+
+    let wall_grid = || {
+        let mut grid = SpatialGrid::default();
+        for wall in &walls {
+            let (min, max) = wall.bounds();
+            grid.insert(min, max, wall.clone());
+        }
+        grid
+    };
+    let character_grid = || {
+        let mut grid = SpatialGrid::default();
+        for &position in &characters {
+            grid.insert_point(position.xz(), position);
+        }
+        grid
+    };
+
+    compare(
+        "move and slide",
+        || {
+            characters
+                .iter()
+                .zip(&steps)
+                .map(|(from, &step)| move_and_slide(from.xz(), step, CHARACTER_RADIUS, &walls))
+                .collect::<Vec<_>>()
+        },
+        || {
+            let grid = wall_grid();
+            characters
+                .iter()
+                .zip(&steps)
+                .map(|(from, &step)| {
+                    let near = grid.near(from.xz(), step.length() + 2.0 * CHARACTER_RADIUS);
+                    move_and_slide(from.xz(), step, CHARACTER_RADIUS, &near)
+                })
+                .collect::<Vec<_>>()
+        },
+    );
+
+    compare(
+        "characters in reach",
+        || {
+            characters
+                .iter()
+                .map(|a| characters.iter().filter(|b| a.distance(**b) < 1.0).count())
+                .collect::<Vec<_>>()
+        },
+        || {
+            let grid = character_grid();
+            characters
+                .iter()
+                .map(|a| {
+                    let near = grid.near(a.xz(), 1.0);
+                    near.into_iter().filter(|b| a.distance(**b) < 1.0).count()
+                })
+                .collect::<Vec<_>>()
+        },
+    );
+
+    // Every character against the next few, like guards looking at whoever is in range.
+    let pairs: Vec<(Vec2, Vec2)> = characters
+        .iter()
+        .enumerate()
+        .flat_map(|(index, a)| {
+            characters[index + 1..]
+                .iter()
+                .take(8)
+                .filter(|b| a.distance(**b) < 20.0)
+                .map(|b| (a.xz(), b.xz()))
+        })
+        .collect();
+    compare(
+        "line of sight",
+        || {
+            pairs
+                .iter()
+                .map(|&(a, b)| walls.iter().any(|wall| wall.blocks(a, b)))
+                .collect::<Vec<_>>()
+        },
+        || {
+            let grid = wall_grid();
+            pairs
+                .iter()
+                .map(|&(a, b)| grid.along(a, b).iter().any(|wall| wall.blocks(a, b)))
+                .collect::<Vec<_>>()
+        },
+    );
+}
+
+/// A maze of rooms, with a door-sized gap left in most walls.
+fn walls(rng: &mut StdRng) -> Vec<Obstacle> {
+    let wall = Collider::Cuboid {
+        half_size: Vec2::new(ROOM_SIZE / 4.0, 0.25),
+    };
+    let mut walls = vec![];
+    for x in 0..ROOMS {
+        for z in 0..ROOMS {
+            let corner = Vec3::new(x as f32, 0.0, z as f32) * ROOM_SIZE;
+            for (offset, rotation) in [
+                (Vec3::X, Quat::IDENTITY),
+                (Vec3::Z, Quat::from_rotation_y(std::f32::consts::FRAC_PI_2)),
+            ] {
+                // Each side is two half walls, one of them left out for a door.
+                let door = rng.gen_range(0..3);
+                for half in 0..2 {
+                    if half == door {
+                        continue;
+                    }
+                    let along = offset * ROOM_SIZE * (0.25 + 0.5 * half as f32);
+                    walls.push(Obstacle::new(&wall, corner + along, rotation));
+                }
+            }
+        }
+    }
+    walls
+}
+
+fn compare<T: PartialEq + std::fmt::Debug>(
+    name: &str,
+    brute_force: impl Fn() -> T,
+    grid: impl Fn() -> T,
+) {
+    assert_eq!(brute_force(), grid(), "{name}: the grid changed the result");
+    let brute_force = time(brute_force);
+    let grid = time(grid);
+    println!(
+        "{name:>20}: {:>10.3?} going through everything, {:>10.3?} with the grid ({:.1}x)",
+        brute_force,
+        grid,
+        brute_force.as_secs_f64() / grid.as_secs_f64(),
+    );
+}
+
+/// Average time of one run.
+fn time<T>(run: impl Fn() -> T) -> Duration {
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        black_box(run());
+    }
+    start.elapsed() / ITERATIONS
+}
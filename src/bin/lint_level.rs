@@ -0,0 +1,53 @@
+//! Check level files for problems, without starting the game.
+//! Pass the level files to check, or nothing to check every level in `assets/levels`.
+//! Exits with an error if any level has problems, so it can gate level changes in CI.
+
+use std::{fs, path::PathBuf, process::ExitCode};
+
+use jamprogamer::{validate_level, Level};
+
+fn main() -> ExitCode {
+    let mut paths: Vec<PathBuf> = std::env::args().skip(1).map(PathBuf::from).collect();
+    if paths.is_empty() {
+        let entries = match fs::read_dir("assets/levels") {
+            Ok(entries) => entries,
+            Err(error) => {
+                eprintln!("could not list assets/levels: {error}");
+                return ExitCode::FAILURE;
+            }
+        };
+        paths = entries
+            .filter_map(|entry| Some(entry.ok()?.path()))
+            .filter(|path| path.to_string_lossy().ends_with(".level.ron"))
+            .collect();
+        paths.sort();
+    }
+
+    let mut failed = false;
+    for path in &paths {
+        let level = match fs::read_to_string(path)
+            .map_err(|error| error.to_string())
+            .and_then(|text| Level::from_ron(&text).map_err(|error| error.to_string()))
+        {
+            Ok(level) => level,
+            Err(error) => {
+                eprintln!("{}: {error}", path.display());
+                failed = true;
+                continue;
+            }
+        };
+        let issues = validate_level(&level);
+        if issues.is_empty() {
+            println!("{}: ok", path.display());
+        }
+        for issue in &issues {
+            eprintln!("{}: {issue}", path.display());
+        }
+        failed |= !issues.is_empty();
+    }
+
+    match failed {
+        true => ExitCode::FAILURE,
+        false => ExitCode::SUCCESS,
+    }
+}
@@ -4,6 +4,8 @@ use bevy::{
     utils::HashMap,
 };
 
+use super::level::Level;
+
 pub(super) fn plugin(app: &mut App) {
     app.register_type::<HandleMap<ImageKey>>();
     app.init_resource::<HandleMap<ImageKey>>();
@@ -26,6 +28,9 @@ pub(super) fn plugin(app: &mut App) {
     app.register_type::<HandleMap<GraphKey>>();
     app.init_resource::<HandleMap<GraphKey>>();
 
+    app.register_type::<HandleMap<LevelKey>>();
+    app.init_resource::<HandleMap<LevelKey>>();
+
     app.register_type::<Animations>();
     app.register_type::<Action>();
 
@@ -38,7 +43,7 @@ pub struct Action{
     pub new_track:NlaTrack,
 }
 
-#[derive(Copy, Clone, Eq, PartialEq, Hash, Reflect)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Reflect)]
 pub enum NlaTrack{
     Walk,
     Idle,
@@ -95,6 +100,37 @@ impl FromWorld for HandleMap<GraphKey> {
     }
 }
 
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Reflect)]
+pub enum LevelKey {
+    Stage1,
+    Stage2,
+    Stage3,
+}
+
+impl AssetKey for LevelKey {
+    type Asset = Level;
+}
+
+impl LevelKey {
+    /// Location of the level file, relative to the assets folder.
+    pub fn path(self) -> &'static str {
+        match self {
+            LevelKey::Stage1 => "levels/stage1.level.ron",
+            LevelKey::Stage2 => "levels/stage2.level.ron",
+            LevelKey::Stage3 => "levels/stage3.level.ron",
+        }
+    }
+}
+
+impl FromWorld for HandleMap<LevelKey> {
+    fn from_world(world: &mut World) -> Self {
+        let asset_server = world.resource::<AssetServer>();
+        [LevelKey::Stage1, LevelKey::Stage2, LevelKey::Stage3]
+            .map(|key| (key, asset_server.load(key.path())))
+            .into()
+    }
+}
+
 #[derive(Copy, Clone, Eq, PartialEq, Hash, Reflect)]
 pub enum SceneKey {
     Character,
@@ -145,6 +181,7 @@ pub enum MeshKey {
     Capsule,
     Floor,
     Wall,
+    Pillar,
 }
 
 impl AssetKey for MeshKey {
@@ -158,6 +195,7 @@ impl FromWorld for HandleMap<MeshKey> {
             (MeshKey::Capsule,asset_server.add(Capsule3d::default().into())),
             (MeshKey::Floor,asset_server.add(Plane3d::new(*Dir3::Y,Vec2::new(200.0,200.0)).into())),
             (MeshKey::Wall,asset_server.add(Cuboid::from_length(2.0).into())),
+            (MeshKey::Pillar,asset_server.add(Cylinder::new(1.0,2.0).into())),
         ]
         .into()
     }
@@ -0,0 +1,110 @@
+//! The ordered list of levels and the player's progress through them.
+
+use bevy::prelude::*;
+
+use super::{
+    assets::{HandleMap, LevelKey},
+    generator::generate_level,
+    level::Level,
+};
+use crate::screen::Screen;
+
+pub(super) fn plugin(app: &mut App) {
+    app.register_type::<Campaign>();
+    app.init_resource::<Campaign>();
+
+    app.register_type::<CurrentLevel>();
+    app.init_resource::<CurrentLevel>();
+
+    app.add_systems(OnEnter(Screen::Win), unlock_next_level);
+}
+
+/// Levels in the order they are played.
+pub const CAMPAIGN: [LevelKey; 3] = [LevelKey::Stage1, LevelKey::Stage2, LevelKey::Stage3];
+
+#[derive(Resource, Debug, Reflect)]
+#[reflect(Resource)]
+pub struct Campaign {
+    /// How many levels of [`CAMPAIGN`] can be played, counting from the first.
+    pub unlocked: usize,
+}
+
+impl Default for Campaign {
+    fn default() -> Self {
+        Self { unlocked: 1 }
+    }
+}
+
+impl Campaign {
+    pub fn is_unlocked(&self, index: usize) -> bool {
+        index < self.unlocked.min(CAMPAIGN.len())
+    }
+}
+
+/// The level that [`SpawnLevel`](super::spawn::level::SpawnLevel) will build.
+#[derive(Resource, Debug, Clone, Reflect)]
+#[reflect(Resource)]
+pub struct CurrentLevel {
+    pub handle: Handle<Level>,
+    /// Position in [`CAMPAIGN`], if this level is part of it.
+    pub campaign_index: Option<usize>,
+    /// Seed the level was generated from, if it was generated.
+    pub seed: Option<u64>,
+}
+
+impl CurrentLevel {
+    pub fn campaign(index: usize, level_handles: &HandleMap<LevelKey>) -> Self {
+        Self {
+            handle: level_handles[&CAMPAIGN[index]].clone_weak(),
+            campaign_index: Some(index),
+            seed: None,
+        }
+    }
+
+    pub fn generated(seed: u64, levels: &mut Assets<Level>) -> Self {
+        Self {
+            handle: levels.add(generate_level(seed)),
+            campaign_index: None,
+            seed: Some(seed),
+        }
+    }
+
+    /// Rebuild a level from where it came from, for saves and replays.
+    /// Levels from anywhere else, like the editor, can't be rebuilt.
+    pub fn restore(
+        campaign_index: Option<usize>,
+        seed: Option<u64>,
+        level_handles: &HandleMap<LevelKey>,
+        levels: &mut Assets<Level>,
+    ) -> Option<Self> {
+        match (campaign_index, seed) {
+            (Some(index), _) if index < CAMPAIGN.len() => Some(Self::campaign(index, level_handles)),
+            (_, Some(seed)) => Some(Self::generated(seed, levels)),
+            _ => None,
+        }
+    }
+
+    /// Whether [`CurrentLevel::restore`] can build this level again.
+    pub fn is_restorable(&self) -> bool {
+        self.campaign_index.is_some() || self.seed.is_some()
+    }
+
+    /// The campaign level after this one, if there is one.
+    pub fn next(&self) -> Option<usize> {
+        self.campaign_index
+            .map(|index| index + 1)
+            .filter(|&index| index < CAMPAIGN.len())
+    }
+}
+
+impl FromWorld for CurrentLevel {
+    fn from_world(world: &mut World) -> Self {
+        CurrentLevel::campaign(0, world.resource::<HandleMap<LevelKey>>())
+    }
+}
+
+fn unlock_next_level(current_level: Res<CurrentLevel>, mut campaign: ResMut<Campaign>) {
+    if let Some(next) = current_level.next() {
+        campaign.unlocked = campaign.unlocked.max(next + 1);
+    }
+}
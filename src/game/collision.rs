@@ -0,0 +1,346 @@
+//! Keeps characters out of walls, and walls between characters and what they look at.
+//! Characters are circles on the floor plane. They move in small steps and
+//! are pushed out of anything they overlap after each step, which makes them
+//! slide along walls and around corners instead of stopping dead.
+
+use std::borrow::Borrow;
+
+use bevy::prelude::*;
+
+pub(super) fn plugin(app: &mut App) {
+    app.register_type::<Collider>();
+}
+
+/// Radius of every character, player, guard or ghost.
+pub const CHARACTER_RADIUS: f32 = 0.4;
+/// Pushing out of one wall can push into another in corners,
+/// so resolve overlaps a few times per step.
+const ITERATIONS: usize = 4;
+/// Shortest step a move is split into, so points and tiny circles still get somewhere.
+const MIN_STEP: f32 = 0.05;
+/// Most steps a move is split into. Moves longer than this many steps can tunnel.
+const MAX_STEPS: usize = 256;
+
+/// The footprint of something solid, seen from above.
+/// It is placed and rotated (around the y axis) by the entity's [`Transform`],
+/// but not scaled by it.
+#[derive(Component, Debug, Clone, PartialEq, Reflect)]
+#[reflect(Component)]
+pub enum Collider {
+    Cuboid { half_size: Vec2 },
+    Circle { radius: f32 },
+    /// A convex outline, relative to the entity.
+    Polygon { points: Vec<Vec2> },
+}
+
+/// A [`Collider`] placed in the world, with floor coordinates `(x, z)`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Obstacle {
+    Circle { center: Vec2, radius: f32 },
+    /// A convex polygon. Boxes become polygons once they are rotated.
+    Polygon { points: Vec<Vec2> },
+}
+
+impl Obstacle {
+    pub fn new(collider: &Collider, translation: Vec3, rotation: Quat) -> Self {
+        let place = |point: Vec2| (translation + rotation * Vec3::new(point.x, 0.0, point.y)).xz();
+        match collider {
+            Collider::Circle { radius } => Obstacle::Circle {
+                center: translation.xz(),
+                radius: *radius,
+            },
+            Collider::Cuboid { half_size } => Obstacle::Polygon {
+                points: [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)]
+                    .map(|(x, y)| place(Vec2::new(x, y) * *half_size))
+                    .to_vec(),
+            },
+            Collider::Polygon { points } => Obstacle::Polygon {
+                points: points.iter().map(|&point| place(point)).collect(),
+            },
+        }
+    }
+
+    pub fn from_transform(collider: &Collider, transform: &Transform) -> Self {
+        Self::new(collider, transform.translation, transform.rotation)
+    }
+
+    /// Corners of the smallest axis aligned rectangle around the obstacle.
+    pub fn bounds(&self) -> (Vec2, Vec2) {
+        match self {
+            Obstacle::Circle { center, radius } => (*center - *radius, *center + *radius),
+            Obstacle::Polygon { points } => points.iter().fold(
+                (Vec2::MAX, Vec2::MIN),
+                |(min, max), &point| (min.min(point), max.max(point)),
+            ),
+        }
+    }
+
+    pub fn contains(&self, point: Vec2) -> bool {
+        match self {
+            Obstacle::Circle { center, radius } => center.distance(point) < *radius,
+            Obstacle::Polygon { points } => {
+                let mut side = 0.0;
+                for (a, b) in edges(points) {
+                    let cross = (b - a).perp_dot(point - a);
+                    if cross.abs() < f32::EPSILON {
+                        continue;
+                    }
+                    if side == 0.0 {
+                        side = cross.signum();
+                    } else if cross.signum() != side {
+                        return false;
+                    }
+                }
+                true
+            }
+        }
+    }
+
+    /// The smallest offset that moves a circle out of this obstacle, if they overlap.
+    pub fn penetration(&self, center: Vec2, radius: f32) -> Option<Vec2> {
+        match self {
+            Obstacle::Circle {
+                center: own_center,
+                radius: own_radius,
+            } => {
+                let offset = center - *own_center;
+                let distance = offset.length();
+                let reach = radius + own_radius;
+                if distance >= reach {
+                    return None;
+                }
+                let direction = offset.try_normalize().unwrap_or(Vec2::X);
+                Some(direction * (reach - distance))
+            }
+            Obstacle::Polygon { points } => {
+                let closest = edges(points)
+                    .map(|(a, b)| closest_on_segment(center, a, b))
+                    .min_by(|a, b| a.distance_squared(center).total_cmp(&b.distance_squared(center)))?;
+                let distance = closest.distance(center);
+                if !self.contains(center) && distance > f32::EPSILON {
+                    if distance >= radius {
+                        return None;
+                    }
+                    return Some((center - closest) / distance * (radius - distance));
+                }
+                // The center is inside, leave through the nearest side.
+                let centroid = points.iter().sum::<Vec2>() / points.len() as f32;
+                edges(points)
+                    .filter_map(|(a, b)| {
+                        let mut normal = (b - a).perp().try_normalize()?;
+                        if normal.dot(a - centroid) < 0.0 {
+                            normal = -normal;
+                        }
+                        Some((normal, normal.dot(a - center)))
+                    })
+                    .min_by(|a, b| a.1.total_cmp(&b.1))
+                    .map(|(normal, depth)| normal * (depth + radius))
+            }
+        }
+    }
+
+    /// Whether this obstacle is in the way of a straight line from `a` to `b`.
+    pub fn blocks(&self, a: Vec2, b: Vec2) -> bool {
+        match self {
+            Obstacle::Circle { center, radius } => {
+                closest_on_segment(*center, a, b).distance(*center) < *radius
+            }
+            Obstacle::Polygon { points } => {
+                self.contains(a)
+                    || self.contains(b)
+                    || edges(points).any(|(c, d)| segments_intersect(a, b, c, d))
+            }
+        }
+    }
+
+    /// How far a ray from `origin` along the unit vector `direction` goes
+    /// before hitting this obstacle, if it does. Rays starting inside hit at once.
+    pub fn ray_hit(&self, origin: Vec2, direction: Vec2) -> Option<f32> {
+        if self.contains(origin) {
+            return Some(0.0);
+        }
+        match self {
+            Obstacle::Circle { center, radius } => {
+                let along = (*center - origin).dot(direction);
+                let closest = (origin + direction * along).distance_squared(*center);
+                let inside = radius * radius - closest;
+                if inside < 0.0 {
+                    return None;
+                }
+                let hit = along - inside.sqrt();
+                (hit >= 0.0).then_some(hit)
+            }
+            Obstacle::Polygon { points } => edges(points)
+                .filter_map(|(a, b)| {
+                    let edge = b - a;
+                    let denominator = direction.perp_dot(edge);
+                    if denominator.abs() < f32::EPSILON {
+                        return None;
+                    }
+                    let hit = (a - origin).perp_dot(edge) / denominator;
+                    let along_edge = (a - origin).perp_dot(direction) / denominator;
+                    (hit >= 0.0 && (0.0..=1.0).contains(&along_edge)).then_some(hit)
+                })
+                .min_by(f32::total_cmp),
+        }
+    }
+}
+
+/// Consecutive pairs of points, including the last back to the first.
+pub fn edges(points: &[Vec2]) -> impl Iterator<Item = (Vec2, Vec2)> + '_ {
+    points
+        .iter()
+        .copied()
+        .zip(points.iter().copied().cycle().skip(1))
+}
+
+pub fn closest_on_segment(point: Vec2, a: Vec2, b: Vec2) -> Vec2 {
+    let segment = b - a;
+    let length_squared = segment.length_squared();
+    if length_squared < f32::EPSILON {
+        return a;
+    }
+    a + segment * ((point - a).dot(segment) / length_squared).clamp(0.0, 1.0)
+}
+
+pub fn segments_intersect(a: Vec2, b: Vec2, c: Vec2, d: Vec2) -> bool {
+    let denominator = (d.y - c.y) * (b.x - a.x) - (d.x - c.x) * (b.y - a.y);
+    let u_a = ((d.x - c.x) * (a.y - c.y) - (d.y - c.y) * (a.x - c.x)) / denominator;
+    let u_b = ((b.x - a.x) * (a.y - c.y) - (b.y - a.y) * (a.x - c.x)) / denominator;
+    (0.0..=1.0).contains(&u_a) && (0.0..=1.0).contains(&u_b)
+}
+
+/// Whether `points` outline a convex polygon that a [`Collider::Polygon`] can use.
+pub fn is_convex(points: &[Vec2]) -> bool {
+    if points.len() < 3 {
+        return false;
+    }
+    let mut side = 0.0;
+    for (index, (a, b)) in edges(points).enumerate() {
+        let c = points[(index + 2) % points.len()];
+        let cross = (b - a).perp_dot(c - b);
+        if cross.abs() < f32::EPSILON {
+            continue;
+        }
+        if side == 0.0 {
+            side = cross.signum();
+        } else if cross.signum() != side {
+            return false;
+        }
+    }
+    side != 0.0
+}
+
+/// Move a circle by `motion`, sliding along any obstacles in the way.
+/// Obstacles can be borrowed, like the ones a [`WallIndex`](super::spatial::WallIndex) hands out.
+/// A motion that isn't finite doesn't move it at all, and a radius below 0 counts as 0.
+pub fn move_and_slide(
+    from: Vec2,
+    motion: Vec2,
+    radius: f32,
+    obstacles: &[impl Borrow<Obstacle>],
+) -> Vec2 {
+    if !motion.is_finite() {
+        return from;
+    }
+    let radius = radius.max(0.0);
+    // Small enough steps that a fast character can't tunnel through a thin wall.
+    let step_length = (radius * 0.5).max(MIN_STEP);
+    let steps = ((motion.length() / step_length).ceil() as usize).clamp(1, MAX_STEPS);
+    let step = motion / steps as f32;
+    let mut position = from;
+    for _ in 0..steps {
+        position += step;
+        position = push_out(position, radius, obstacles);
+    }
+    position
+}
+
+/// Move a circle out of every obstacle it overlaps.
+pub fn push_out(mut position: Vec2, radius: f32, obstacles: &[impl Borrow<Obstacle>]) -> Vec2 {
+    for _ in 0..ITERATIONS {
+        let mut resolved = true;
+        for obstacle in obstacles {
+            if let Some(push) = obstacle.borrow().penetration(position, radius) {
+                position += push;
+                resolved = false;
+            }
+        }
+        if resolved {
+            break;
+        }
+    }
+    position
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::movement::SPRINT_FACTOR;
+
+    /// A wall along z at `x`, `thickness` thick and 10 long.
+    fn wall(x: f32, thickness: f32) -> Obstacle {
+        Obstacle::new(
+            &Collider::Cuboid { half_size: Vec2::new(thickness / 2.0, 5.0) },
+            Vec3::new(x, 0.0, 0.0),
+            Quat::IDENTITY,
+        )
+    }
+
+    #[test]
+    fn circles_push_apart_along_their_centres() {
+        let pillar = Obstacle::Circle { center: Vec2::ZERO, radius: 1.0 };
+        let push = pillar.penetration(Vec2::new(1.2, 0.0), 0.4).unwrap();
+        assert!(push.abs_diff_eq(Vec2::new(0.2, 0.0), 1e-5), "{push}");
+        assert_eq!(pillar.penetration(Vec2::new(1.5, 0.0), 0.4), None);
+        // Dead centre still gets pushed somewhere.
+        assert!(pillar.penetration(Vec2::ZERO, 0.4).is_some());
+    }
+
+    #[test]
+    fn polygons_push_out_through_the_nearest_edge() {
+        let wall = wall(0.0, 2.0);
+        // Overlapping the edge from outside.
+        let push = wall.penetration(Vec2::new(1.3, 0.0), 0.4).unwrap();
+        assert!(push.abs_diff_eq(Vec2::new(0.1, 0.0), 1e-5), "{push}");
+        // Inside, closer to the left edge.
+        let push = wall.penetration(Vec2::new(-0.8, 0.0), 0.4).unwrap();
+        assert!(push.abs_diff_eq(Vec2::new(-0.6, 0.0), 1e-5), "{push}");
+        assert_eq!(wall.penetration(Vec2::new(1.5, 0.0), 0.4), None);
+        // Rotated boxes are polygons too.
+        let diamond = Obstacle::new(
+            &Collider::Cuboid { half_size: Vec2::ONE },
+            Vec3::ZERO,
+            Quat::from_rotation_y(std::f32::consts::FRAC_PI_4),
+        );
+        assert!(diamond.penetration(Vec2::new(1.5, 0.0), 0.4).is_some());
+        assert_eq!(diamond.penetration(Vec2::new(1.0, 1.0), 0.4), None);
+    }
+
+    #[test]
+    fn slides_along_a_wall() {
+        let walls = [wall(1.0, 1.0)];
+        let to = move_and_slide(Vec2::ZERO, Vec2::new(1.0, 1.0), CHARACTER_RADIUS, &walls);
+        assert!(to.x <= 0.5 - CHARACTER_RADIUS + 1e-4, "{to}");
+        assert!(to.y > 0.9, "{to}");
+    }
+
+    #[test]
+    fn does_not_tunnel_through_a_thin_wall_at_sprint_speed() {
+        let walls = [wall(1.0, 0.1)];
+        // Half a second of sprinting in one go, far more than a tick.
+        let motion = Vec2::X * 5.5 * SPRINT_FACTOR * 0.5;
+        let to = move_and_slide(Vec2::ZERO, motion, CHARACTER_RADIUS, &walls);
+        assert!(to.x < 1.0, "{to}");
+    }
+
+    #[test]
+    fn odd_radius_or_motion_does_not_hang() {
+        let walls = [wall(1.0, 0.1)];
+        let to = move_and_slide(Vec2::ZERO, Vec2::X * 100.0, 0.0, &walls);
+        assert!(to.is_finite());
+        assert_eq!(move_and_slide(Vec2::ZERO, Vec2::NAN, 0.4, &walls), Vec2::ZERO);
+        assert_eq!(move_and_slide(Vec2::ZERO, Vec2::X * f32::INFINITY, 0.4, &walls), Vec2::ZERO);
+        assert!(move_and_slide(Vec2::ZERO, Vec2::X, -1.0, &walls).is_finite());
+    }
+}
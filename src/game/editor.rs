@@ -0,0 +1,699 @@
+//! Authoring tools for level files, used by the editor screen.
+//! Everything is drawn with gizmos straight from the [`Level`] being edited,
+//! so there are no entities to keep in sync with the data.
+
+use std::f32::consts::PI;
+
+use bevy::{input::mouse::MouseWheel, prelude::*, window::PrimaryWindow};
+
+use super::{
+    collision::{edges, Obstacle},
+    guards::{camera_pose, GuardKind},
+    level::{DoorData, FurnaceData, GuardData, ItemData, Level, SwitchData, WallData, WallShape},
+    movement::sample_path,
+    validate::validate_level,
+    world::Persistence,
+};
+use crate::{screen::Screen, AppSet};
+
+pub(super) fn plugin(app: &mut App) {
+    app.register_type::<EditorTool>();
+    app.add_systems(
+        Update,
+        (
+            (select_tool, scrub_timeline, move_editor_camera).in_set(AppSet::RecordInput),
+            (use_tool, edit_selection, save_level)
+                .chain()
+                .in_set(AppSet::Update),
+            draw_level.after(AppSet::Update),
+        )
+            .run_if(in_state(Screen::Editor)),
+    );
+}
+
+/// Snapping for everything placed with the mouse.
+const GRID: f32 = 1.0;
+/// How far the timeline moves per key press, in seconds.
+const TIMELINE_STEP: f32 = 0.5;
+const CAMERA_SPEED: f32 = 20.0;
+
+/// The level being edited and the state of the editing tools.
+#[derive(Resource, Debug)]
+pub struct LevelEditor {
+    pub level: Level,
+    /// Where the level is saved, relative to the assets folder.
+    pub file: String,
+    pub tool: EditorTool,
+    pub selection: Option<Selection>,
+    /// Timestamp given to guard waypoints, and the time guards are previewed at.
+    pub time: f32,
+    drag_start: Option<Vec3>,
+}
+
+impl LevelEditor {
+    pub fn new(level: Level, file: impl Into<String>) -> Self {
+        Self {
+            level,
+            file: file.into(),
+            tool: EditorTool::Select,
+            selection: None,
+            time: 0.0,
+            drag_start: None,
+        }
+    }
+
+    /// The waypoints of the selected guard, if a guard is selected.
+    pub fn selected_path(&self) -> Option<&[(f32, Vec3)]> {
+        match self.selection {
+            Some(Selection::Guard(index)) => Some(&self.level.guards[index].path),
+            _ => None,
+        }
+    }
+
+    fn pick(&self, point: Vec3) -> Option<Selection> {
+        if let Some(index) = self
+            .level
+            .furnaces
+            .iter()
+            .position(|furnace| furnace.translation.xz().distance(point.xz()) < 1.0)
+        {
+            return Some(Selection::Furnace(index));
+        }
+        if let Some(index) = self.level.guards.iter().position(|guard| {
+            guard
+                .path
+                .iter()
+                .any(|(_, waypoint)| waypoint.xz().distance(point.xz()) < 1.0)
+        }) {
+            return Some(Selection::Guard(index));
+        }
+        if let Some(index) = self
+            .level
+            .switches
+            .iter()
+            .position(|switch| switch.translation.xz().distance(point.xz()) < 1.0)
+        {
+            return Some(Selection::Switch(index));
+        }
+        if let Some(index) = self
+            .level
+            .items
+            .iter()
+            .position(|item| item.translation.xz().distance(point.xz()) < 1.0)
+        {
+            return Some(Selection::Item(index));
+        }
+        if let Some(index) = self
+            .level
+            .doors
+            .iter()
+            .position(|door| door.obstacle().contains(point.xz()))
+        {
+            return Some(Selection::Door(index));
+        }
+        self.level
+            .walls
+            .iter()
+            .position(|wall| wall.obstacle().contains(point.xz()))
+            .map(Selection::Wall)
+    }
+
+    fn delete_selection(&mut self) {
+        match self.selection.take() {
+            Some(Selection::Wall(index)) => {
+                self.level.walls.remove(index);
+            }
+            Some(Selection::Furnace(index)) => {
+                self.level.furnaces.remove(index);
+            }
+            Some(Selection::Guard(index)) => {
+                self.level.guards.remove(index);
+            }
+            // Nothing else should go on opening what was deleted.
+            Some(Selection::Door(index)) => {
+                let door = self.level.doors.remove(index);
+                for switch in &mut self.level.switches {
+                    switch.doors.retain(|name| *name != door.name);
+                }
+            }
+            Some(Selection::Switch(index)) => {
+                self.level.switches.remove(index);
+            }
+            Some(Selection::Item(index)) => {
+                let item = self.level.items.remove(index);
+                for door in &mut self.level.doors {
+                    if door.key.as_ref() == Some(&item.name) {
+                        door.key = None;
+                    }
+                }
+            }
+            None => {}
+        }
+    }
+
+    /// The selected door, for linking a new switch or key to it.
+    fn selected_door(&mut self) -> Option<&mut DoorData> {
+        match self.selection {
+            Some(Selection::Door(index)) => Some(&mut self.level.doors[index]),
+            _ => None,
+        }
+    }
+
+    /// Names are how switches and doors find each other, so keep them unique across the level.
+    fn unique_name(&self, prefix: &str) -> String {
+        let level = &self.level;
+        unique_name(
+            prefix,
+            level
+                .doors
+                .iter()
+                .map(|door| &door.name)
+                .chain(level.switches.iter().map(|switch| &switch.name))
+                .chain(level.items.iter().map(|item| &item.name)),
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Reflect)]
+pub enum EditorTool {
+    /// Click to select, arrows to resize walls, shift + arrows to move things.
+    Select,
+    /// Drag on the floor to place a wall.
+    Wall,
+    /// Click to drop a furnace.
+    Furnace,
+    /// Click to add a waypoint at the timeline's time to the selected guard,
+    /// or to a new guard if none is selected.
+    Guard,
+    /// Click to move the player's start.
+    PlayerStart,
+    /// Drag on the floor to place a door.
+    Door,
+    /// Click to drop a switch, which opens the selected door.
+    Switch,
+    /// Click to drop an item, which becomes the key of the selected door.
+    Item,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Selection {
+    Wall(usize),
+    Furnace(usize),
+    Guard(usize),
+    Door(usize),
+    Switch(usize),
+    Item(usize),
+}
+
+fn select_tool(input: Res<ButtonInput<KeyCode>>, mut editor: ResMut<LevelEditor>) {
+    let tool = if input.just_pressed(KeyCode::Digit1) {
+        EditorTool::Select
+    } else if input.just_pressed(KeyCode::Digit2) {
+        EditorTool::Wall
+    } else if input.just_pressed(KeyCode::Digit3) {
+        EditorTool::Furnace
+    } else if input.just_pressed(KeyCode::Digit4) {
+        EditorTool::Guard
+    } else if input.just_pressed(KeyCode::Digit5) {
+        EditorTool::PlayerStart
+    } else if input.just_pressed(KeyCode::Digit6) {
+        EditorTool::Door
+    } else if input.just_pressed(KeyCode::Digit7) {
+        EditorTool::Switch
+    } else if input.just_pressed(KeyCode::Digit8) {
+        EditorTool::Item
+    } else {
+        return;
+    };
+    editor.tool = tool;
+    editor.drag_start = None;
+}
+
+fn scrub_timeline(input: Res<ButtonInput<KeyCode>>, mut editor: ResMut<LevelEditor>) {
+    if input.just_pressed(KeyCode::BracketLeft) {
+        editor.level.max_time = (editor.level.max_time - 1.0).max(1.0);
+    }
+    if input.just_pressed(KeyCode::BracketRight) {
+        editor.level.max_time += 1.0;
+    }
+    let mut time = editor.time;
+    if input.just_pressed(KeyCode::KeyQ) {
+        time -= TIMELINE_STEP;
+    }
+    if input.just_pressed(KeyCode::KeyE) {
+        time += TIMELINE_STEP;
+    }
+    editor.time = time.clamp(0.0, editor.level.max_time);
+}
+
+fn move_editor_camera(
+    time: Res<Time>,
+    input: Res<ButtonInput<KeyCode>>,
+    mut wheel: EventReader<MouseWheel>,
+    mut camera: Query<&mut Transform, With<Camera3d>>,
+) {
+    let Ok(mut camera) = camera.get_single_mut() else {
+        return;
+    };
+    let zoom: f32 = wheel.read().map(|event| event.y).sum();
+    if zoom != 0.0 {
+        let forward = camera.forward();
+        camera.translation += forward * zoom * 2.0;
+    }
+    if input.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]) {
+        return;
+    }
+    let mut intent = Vec3::ZERO;
+    if input.pressed(KeyCode::KeyW) {
+        intent.z += 1.0;
+    }
+    if input.pressed(KeyCode::KeyS) {
+        intent.z -= 1.0;
+    }
+    if input.pressed(KeyCode::KeyA) {
+        intent.x += 1.0;
+    }
+    if input.pressed(KeyCode::KeyD) {
+        intent.x -= 1.0;
+    }
+    camera.translation += intent.normalize_or_zero() * CAMERA_SPEED * time.delta_seconds();
+}
+
+/// The point on the floor under the mouse cursor, snapped to the grid.
+pub fn cursor_on_floor(
+    window: &Window,
+    camera: &Camera,
+    camera_transform: &GlobalTransform,
+) -> Option<Vec3> {
+    let cursor = window.cursor_position()?;
+    let ray = camera.viewport_to_world(camera_transform, cursor)?;
+    let distance = ray.intersect_plane(Vec3::ZERO, InfinitePlane3d::new(Vec3::Y))?;
+    let point = ray.get_point(distance);
+    Some((point / GRID).round() * GRID)
+}
+
+fn use_tool(
+    mouse: Res<ButtonInput<MouseButton>>,
+    window: Query<&Window, With<PrimaryWindow>>,
+    camera: Query<(&Camera, &GlobalTransform), With<Camera3d>>,
+    mut editor: ResMut<LevelEditor>,
+) {
+    let (Ok(window), Ok((camera, camera_transform))) = (window.get_single(), camera.get_single())
+    else {
+        return;
+    };
+    let Some(point) = cursor_on_floor(window, camera, camera_transform) else {
+        return;
+    };
+    if mouse.just_pressed(MouseButton::Right) {
+        editor.selection = None;
+        editor.drag_start = None;
+    }
+    let editor = editor.as_mut();
+    match editor.tool {
+        EditorTool::Select if mouse.just_pressed(MouseButton::Left) => {
+            editor.selection = editor.pick(point);
+        }
+        EditorTool::Wall | EditorTool::Door if mouse.just_pressed(MouseButton::Left) => {
+            editor.drag_start = Some(point);
+        }
+        EditorTool::Wall if mouse.just_released(MouseButton::Left) => {
+            let Some(start) = editor.drag_start.take() else {
+                return;
+            };
+            let center = (start + point) / 2.0;
+            let half_size = ((point - start).abs() / 2.0).max(Vec3::splat(GRID / 2.0));
+            editor.level.walls.push(WallData {
+                translation: Vec3::new(center.x, 2.0, center.z),
+                scale: Vec3::new(half_size.x, 5.0, half_size.z),
+                rotation: 0.0,
+                shape: WallShape::Box,
+            });
+            editor.selection = Some(Selection::Wall(editor.level.walls.len() - 1));
+        }
+        EditorTool::Furnace if mouse.just_pressed(MouseButton::Left) => {
+            let name = unique_name(
+                "Furnace",
+                editor.level.furnaces.iter().map(|furnace| &furnace.name),
+            );
+            editor.level.furnaces.push(FurnaceData {
+                name,
+                translation: Vec3::new(point.x, 1.0, point.z),
+            });
+            editor.selection = Some(Selection::Furnace(editor.level.furnaces.len() - 1));
+        }
+        EditorTool::Guard if mouse.just_pressed(MouseButton::Left) => {
+            let index = match editor.selection {
+                Some(Selection::Guard(index)) => index,
+                _ => {
+                    let name =
+                        unique_name("Guard", editor.level.guards.iter().map(|guard| &guard.name));
+                    editor.level.guards.push(GuardData {
+                        name,
+                        kind: GuardKind::default(),
+                        path: vec![],
+                    });
+                    editor.level.guards.len() - 1
+                }
+            };
+            let time = editor.time;
+            let path = &mut editor.level.guards[index].path;
+            // Keep waypoints ordered by time; clicking at an existing time moves that waypoint.
+            match path.iter().position(|(waypoint_time, _)| *waypoint_time >= time) {
+                Some(i) if path[i].0 == time => path[i].1 = point,
+                Some(i) => path.insert(i, (time, point)),
+                None => path.push((time, point)),
+            }
+            editor.selection = Some(Selection::Guard(index));
+            editor.time = (time + TIMELINE_STEP * 2.0).min(editor.level.max_time);
+        }
+        EditorTool::PlayerStart if mouse.just_pressed(MouseButton::Left) => {
+            editor.level.player_start = Vec3::new(point.x, 0.0, point.z);
+        }
+        EditorTool::Door if mouse.just_released(MouseButton::Left) => {
+            let Some(start) = editor.drag_start.take() else {
+                return;
+            };
+            let center = (start + point) / 2.0;
+            let half_size = ((point - start).abs() / 2.0).max(Vec3::splat(GRID / 2.0));
+            let name = editor.unique_name("Door");
+            editor.level.doors.push(DoorData {
+                name,
+                translation: Vec3::new(center.x, 2.0, center.z),
+                scale: Vec3::new(half_size.x, 5.0, half_size.z),
+                rotation: 0.0,
+                key: None,
+                persistence: Persistence::default(),
+            });
+            editor.selection = Some(Selection::Door(editor.level.doors.len() - 1));
+        }
+        EditorTool::Switch if mouse.just_pressed(MouseButton::Left) => {
+            let name = editor.unique_name("Switch");
+            let doors = editor.selected_door().map(|door| door.name.clone()).into_iter().collect();
+            editor.level.switches.push(SwitchData {
+                name,
+                translation: Vec3::new(point.x, 0.0, point.z),
+                doors,
+                persistence: Persistence::default(),
+            });
+            editor.selection = Some(Selection::Switch(editor.level.switches.len() - 1));
+        }
+        EditorTool::Item if mouse.just_pressed(MouseButton::Left) => {
+            let name = editor.unique_name("Item");
+            if let Some(door) = editor.selected_door() {
+                door.key = Some(name.clone());
+            }
+            editor.level.items.push(ItemData {
+                name,
+                translation: Vec3::new(point.x, 0.5, point.z),
+                persistence: Persistence::default(),
+            });
+            editor.selection = Some(Selection::Item(editor.level.items.len() - 1));
+        }
+        _ => {}
+    }
+}
+
+fn edit_selection(input: Res<ButtonInput<KeyCode>>, mut editor: ResMut<LevelEditor>) {
+    if input.any_just_pressed([KeyCode::Delete, KeyCode::Backspace]) {
+        editor.delete_selection();
+        return;
+    }
+    if input.just_pressed(KeyCode::Enter) {
+        editor.selection = None;
+        return;
+    }
+    if let Some(Selection::Wall(index)) = editor.selection {
+        let wall = &mut editor.level.walls[index];
+        if input.just_pressed(KeyCode::KeyR) {
+            wall.rotation = (wall.rotation + PI / 12.0) % (2.0 * PI);
+        }
+        if input.just_pressed(KeyCode::KeyT) {
+            wall.shape = match wall.shape {
+                WallShape::Box => WallShape::Pillar,
+                _ => WallShape::Box,
+            };
+        }
+    }
+    if let Some(Selection::Guard(index)) = editor.selection {
+        let guard = &mut editor.level.guards[index];
+        if input.just_pressed(KeyCode::KeyT) {
+            guard.kind = match guard.kind {
+                GuardKind::Guard => GuardKind::Sniper,
+                GuardKind::Sniper => GuardKind::Hound,
+                GuardKind::Hound => GuardKind::Camera,
+                GuardKind::Camera => GuardKind::Heavy,
+                GuardKind::Heavy => GuardKind::Guard,
+            };
+            info!("{} is now a {:?}", guard.name, guard.kind);
+        }
+    }
+    if let Some(Selection::Door(index)) = editor.selection {
+        let door = &mut editor.level.doors[index];
+        if input.just_pressed(KeyCode::KeyR) {
+            door.rotation = (door.rotation + PI / 12.0) % (2.0 * PI);
+        }
+    }
+    if input.just_pressed(KeyCode::KeyT) {
+        let selection = editor.selection;
+        let level = &mut editor.level;
+        let persistence = match selection {
+            Some(Selection::Door(index)) => Some(&mut level.doors[index].persistence),
+            Some(Selection::Switch(index)) => Some(&mut level.switches[index].persistence),
+            Some(Selection::Item(index)) => Some(&mut level.items[index].persistence),
+            _ => None,
+        };
+        if let Some(persistence) = persistence {
+            *persistence = match persistence {
+                Persistence::Reset => Persistence::Keep,
+                Persistence::Keep => Persistence::Reset,
+            };
+            info!("Now {persistence:?} when a new loop begins");
+        }
+    }
+    let mut step = Vec3::ZERO;
+    if input.just_pressed(KeyCode::ArrowLeft) {
+        step.x += GRID / 2.0;
+    }
+    if input.just_pressed(KeyCode::ArrowRight) {
+        step.x -= GRID / 2.0;
+    }
+    if input.just_pressed(KeyCode::ArrowUp) {
+        step.z += GRID / 2.0;
+    }
+    if input.just_pressed(KeyCode::ArrowDown) {
+        step.z -= GRID / 2.0;
+    }
+    if step == Vec3::ZERO {
+        return;
+    }
+    let moving = input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    match editor.selection {
+        Some(Selection::Wall(index)) => {
+            let wall = &mut editor.level.walls[index];
+            if moving {
+                wall.translation += step * 2.0;
+            } else {
+                // The camera looks down +z, so screen right is -x:
+                // right and up grow the wall, left and down shrink it.
+                wall.scale.x = (wall.scale.x - step.x).max(GRID / 2.0);
+                wall.scale.z = (wall.scale.z + step.z).max(GRID / 2.0);
+            }
+        }
+        Some(Selection::Furnace(index)) if moving => {
+            editor.level.furnaces[index].translation += step * 2.0;
+        }
+        Some(Selection::Door(index)) => {
+            let door = &mut editor.level.doors[index];
+            if moving {
+                door.translation += step * 2.0;
+            } else {
+                door.scale.x = (door.scale.x - step.x).max(GRID / 2.0);
+                door.scale.z = (door.scale.z + step.z).max(GRID / 2.0);
+            }
+        }
+        Some(Selection::Switch(index)) if moving => {
+            editor.level.switches[index].translation += step * 2.0;
+        }
+        Some(Selection::Item(index)) if moving => {
+            editor.level.items[index].translation += step * 2.0;
+        }
+        _ => {}
+    }
+}
+
+/// Picks `"{prefix}{n}"` with the lowest `n` that is not taken yet.
+fn unique_name<'a>(prefix: &str, taken: impl Iterator<Item = &'a String> + Clone) -> String {
+    (0..)
+        .map(|n| format!("{prefix}{n}"))
+        .find(|name| !taken.clone().any(|taken| taken == name))
+        .expect("ran out of names")
+}
+
+fn save_level(input: Res<ButtonInput<KeyCode>>, editor: Res<LevelEditor>) {
+    if !(input.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight])
+        && input.just_pressed(KeyCode::KeyS))
+    {
+        return;
+    }
+    for issue in validate_level(&editor.level) {
+        warn!("{}: {issue}", editor.file);
+    }
+    #[cfg(not(target_family = "wasm"))]
+    {
+        let text = match ron::ser::to_string_pretty(&editor.level, default()) {
+            Ok(text) => text,
+            Err(error) => {
+                error!("Could not serialize level: {error}");
+                return;
+            }
+        };
+        let path = std::path::Path::new("assets").join(&editor.file);
+        match std::fs::write(&path, text) {
+            Ok(()) => info!("Saved level to {}", path.display()),
+            Err(error) => error!("Could not save level to {}: {error}", path.display()),
+        }
+    }
+    #[cfg(target_family = "wasm")]
+    warn!("Saving {} is not supported on the web", editor.file);
+}
+
+fn draw_level(
+    mut gizmos: Gizmos,
+    editor: Res<LevelEditor>,
+    window: Query<&Window, With<PrimaryWindow>>,
+    camera: Query<(&Camera, &GlobalTransform), With<Camera3d>>,
+) {
+    const WALL: Color = Color::srgb(0.3, 0.3, 1.0);
+    const FURNACE: Color = Color::srgb(1.0, 0.2, 0.0);
+    const PATH: Color = Color::srgb(0.8, 0.8, 0.8);
+    const START: Color = Color::srgb(0.0, 1.0, 0.2);
+    const WORLD_OBJECT: Color = Color::srgb(0.0, 0.8, 0.6);
+    /// World objects that stay the way the last loop left them.
+    const KEPT: Color = Color::srgb(0.8, 0.0, 0.8);
+    const SELECTED: Color = Color::srgb(1.0, 1.0, 0.0);
+
+    let level = &editor.level;
+    let color = |selection, base| {
+        if editor.selection == Some(selection) {
+            SELECTED
+        } else {
+            base
+        }
+    };
+
+    for (index, wall) in level.walls.iter().enumerate() {
+        let color = color(Selection::Wall(index), WALL);
+        let top = wall.translation.y + wall.scale.y;
+        match wall.obstacle() {
+            Obstacle::Circle { center, radius } => {
+                for y in [0.0, top] {
+                    gizmos.circle(Vec3::new(center.x, y, center.y), Dir3::Y, radius, color);
+                }
+            }
+            Obstacle::Polygon { points } => {
+                for (a, b) in edges(&points) {
+                    gizmos.line(Vec3::new(a.x, 0.0, a.y), Vec3::new(b.x, 0.0, b.y), color);
+                    gizmos.line(Vec3::new(a.x, top, a.y), Vec3::new(b.x, top, b.y), color);
+                    gizmos.line(Vec3::new(a.x, 0.0, a.y), Vec3::new(a.x, top, a.y), color);
+                }
+            }
+        }
+    }
+    for (index, furnace) in level.furnaces.iter().enumerate() {
+        let color = color(Selection::Furnace(index), FURNACE);
+        gizmos.sphere(furnace.translation, Quat::IDENTITY, 0.5, color);
+        // Characters closer than this keep the furnace burning.
+        gizmos.circle(furnace.translation.with_y(0.0), Dir3::Y, 3.0, color);
+    }
+    for (index, guard) in level.guards.iter().enumerate() {
+        let color = color(Selection::Guard(index), PATH);
+        let points = guard.path.iter().map(|(_, point)| *point);
+        gizmos.linestrip(points.clone().chain(points.take(1)), color);
+        for (_, point) in &guard.path {
+            gizmos.sphere(*point, Quat::IDENTITY, 0.2, color);
+        }
+        let profile = guard.kind.profile();
+        let pose = if profile.speed > 0.0 {
+            sample_path(&guard.path, editor.time, level.max_time).map(|(position, direction)| {
+                Transform::from_translation(position).looking_to(direction, Vec3::Y)
+            })
+        } else {
+            (!guard.path.is_empty()).then(|| camera_pose(&guard.path, editor.time, level.max_time))
+        };
+        if let Some(pose) = pose {
+            gizmos.circle(pose.translation, Dir3::Y, 0.5 * profile.scale, color);
+            // The edges of what it sees in full light.
+            for side in [-1.0, 1.0] {
+                let edge = Quat::from_rotation_y(side * profile.view_half_angle) * *pose.forward();
+                let end = pose.translation + edge * profile.view_distance;
+                gizmos.line(pose.translation, end, color);
+            }
+        }
+    }
+    let world_object = |selection, persistence| {
+        color(
+            selection,
+            match persistence {
+                Persistence::Reset => WORLD_OBJECT,
+                Persistence::Keep => KEPT,
+            },
+        )
+    };
+    let door_center = |name: &String| {
+        level
+            .doors
+            .iter()
+            .find(|door| door.name == *name)
+            .map(|door| door.translation.with_y(0.0))
+    };
+    for (index, door) in level.doors.iter().enumerate() {
+        let color = world_object(Selection::Door(index), door.persistence);
+        if let Obstacle::Polygon { points } = door.obstacle() {
+            let top = door.translation.y + door.scale.y;
+            for (a, b) in edges(&points) {
+                gizmos.line(Vec3::new(a.x, 0.0, a.y), Vec3::new(b.x, 0.0, b.y), color);
+                gizmos.line(Vec3::new(a.x, top, a.y), Vec3::new(b.x, top, b.y), color);
+            }
+        }
+    }
+    for (index, switch) in level.switches.iter().enumerate() {
+        let color = world_object(Selection::Switch(index), switch.persistence);
+        let position = switch.translation.with_y(0.0);
+        gizmos.circle(position, Dir3::Y, 0.6, color);
+        // What it opens.
+        for door in switch.doors.iter().filter_map(door_center) {
+            gizmos.line(position, door, color);
+        }
+    }
+    for (index, item) in level.items.iter().enumerate() {
+        let color = world_object(Selection::Item(index), item.persistence);
+        gizmos.sphere(item.translation, Quat::IDENTITY, 0.3, color);
+        let opens = level
+            .doors
+            .iter()
+            .filter(|door| door.key.as_ref() == Some(&item.name));
+        for door in opens {
+            gizmos.line(item.translation, door.translation.with_y(0.0), color);
+        }
+    }
+    gizmos.circle(level.player_start, Dir3::Y, 0.5, START);
+
+    let (Ok(window), Ok((camera, camera_transform))) = (window.get_single(), camera.get_single())
+    else {
+        return;
+    };
+    if let Some(point) = cursor_on_floor(window, camera, camera_transform) {
+        gizmos.circle(point, Dir3::Y, 0.25, SELECTED);
+        if let Some(start) = editor.drag_start {
+            let center = (start + point) / 2.0;
+            let size = (point - start).abs().xz().max(Vec2::splat(GRID));
+            gizmos.rect(
+                center,
+                Quat::from_rotation_x(std::f32::consts::FRAC_PI_2),
+                size,
+                SELECTED,
+            );
+        }
+    }
+}
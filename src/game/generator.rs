@@ -0,0 +1,387 @@
+//! Seeded procedural levels.
+//! The map is a grid of square rooms joined by doors. The doors come from a
+//! random spanning tree over the rooms, so every room (and every furnace in it)
+//! can be reached from the start, plus a few extra doors to make loops.
+//! The same seed always produces the same [`Level`]. It uses ChaCha8 rather
+//! than `StdRng`, whose output may change between rand versions, since saves,
+//! replays and shared or daily seeds store only the seed.
+
+use bevy::{prelude::*, utils::SystemTime};
+use rand::{
+    seq::{IteratorRandom, SliceRandom},
+    Rng, SeedableRng,
+};
+use rand_chacha::ChaCha8Rng;
+
+use super::{
+    ghosts::DEFAULT_MAX_GHOSTS,
+    guards::{GuardKind, DEFAULT_ALARM_BODIES},
+    level::{FurnaceData, GuardData, Level, WallData, WallShape},
+    paradox::ParadoxRule,
+};
+
+/// Side length of one room.
+const ROOM: f32 = 16.0;
+/// Width of the gap left in a wall for a door.
+const DOOR: f32 = 4.0;
+/// Half the thickness of every wall.
+const THICKNESS: f32 = 0.5;
+const WALL_HEIGHT: f32 = 5.0;
+/// Chance for a wall that is not needed for connectivity to get a door anyway.
+const EXTRA_DOOR_CHANCE: f64 = 0.15;
+const FURNACES: usize = 3;
+/// What generated guards can be, plain guards twice as often as the rest.
+const GUARD_KINDS: [GuardKind; 6] = [
+    GuardKind::Guard,
+    GuardKind::Guard,
+    GuardKind::Sniper,
+    GuardKind::Hound,
+    GuardKind::Camera,
+    GuardKind::Heavy,
+];
+/// Mixed into the seed for picking guard kinds, which has its own generator so
+/// adding or retuning kinds doesn't move a seed's walls, furnaces or patrols.
+const KIND_SALT: u64 = 0x6b69_6e64;
+
+/// The seed of today's daily challenge, the same for every player on a given (UTC) day.
+pub fn daily_seed() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |since_epoch| since_epoch.as_secs() / (24 * 60 * 60))
+}
+
+pub fn generate_level(seed: u64) -> Level {
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    let width = rng.gen_range(3..=4);
+    let height = rng.gen_range(3..=4);
+    let max_time = 5.0 * rng.gen_range(5..=8) as f32;
+
+    let layout = Layout::generate(&mut rng, width, height);
+
+    let start_room = IVec2::ZERO;
+    let mut rooms: Vec<IVec2> = layout.rooms().filter(|&room| room != start_room).collect();
+    rooms.shuffle(&mut rng);
+
+    let furnace_rooms: Vec<IVec2> = rooms.iter().copied().take(FURNACES).collect();
+    let furnaces = furnace_rooms
+        .iter()
+        .enumerate()
+        .map(|(index, &room)| FurnaceData {
+            name: format!("Furnace{index}"),
+            translation: room_center(room).with_y(1.0),
+        })
+        .collect();
+
+    let mut walls = layout.walls();
+    // Rooms without a furnace sometimes get a pillar to hide behind.
+    for &room in rooms.iter().skip(FURNACES) {
+        if rng.gen_bool(0.5) {
+            let half_size = rng.gen_range(1.0..2.0);
+            let shape = if rng.gen_bool(0.5) { WallShape::Pillar } else { WallShape::Box };
+            walls.push(WallData {
+                translation: room_center(room).with_y(2.0),
+                scale: Vec3::new(half_size, WALL_HEIGHT, half_size),
+                rotation: rng.gen_range(0.0..std::f32::consts::FRAC_PI_2),
+                shape,
+            });
+        }
+    }
+
+    let mut kind_rng = ChaCha8Rng::seed_from_u64(seed ^ KIND_SALT);
+    let guard_count = (rooms.len() / 2).max(1);
+    let guards = rooms
+        .iter()
+        .take(guard_count)
+        .enumerate()
+        .map(|(index, &room)| {
+            let door = layout
+                .neighbours(room)
+                .filter(|&(other, _)| other != start_room)
+                .choose(&mut rng);
+            let points = match door {
+                Some((other, door)) if rng.gen_bool(0.5) => {
+                    // Walk back and forth through the door between two rooms.
+                    let into_other = (room_center(other) - room_center(room)).normalize() * 5.0;
+                    vec![door - into_other, door, door + into_other, door]
+                }
+                _ => {
+                    // Walk around the inside of the room.
+                    let inset = rng.gen_range(2.5..4.0);
+                    let min = room_corner(room) + Vec3::new(inset, 0.0, inset);
+                    let max = room_corner(room + IVec2::ONE) - Vec3::new(inset, 0.0, inset);
+                    let mut corners = vec![
+                        min,
+                        Vec3::new(max.x, 0.0, min.z),
+                        max,
+                        Vec3::new(min.x, 0.0, max.z),
+                    ];
+                    if rng.gen_bool(0.5) {
+                        corners.reverse();
+                    }
+                    corners
+                }
+            };
+            // The first guard has to be one that can die, or the level is won from the start.
+            let kind = GUARD_KINDS
+                .into_iter()
+                .filter(|kind| index > 0 || kind.can_be_killed())
+                .choose(&mut kind_rng)
+                .unwrap_or_default();
+            GuardData {
+                name: format!("Guard{index}"),
+                kind,
+                path: loop_path(&points, max_time, rng.gen_range(0.0..max_time)),
+            }
+        })
+        .collect();
+
+    Level {
+        name: format!("Seed {seed}"),
+        max_time,
+        player_start: room_center(start_room).with_y(0.0),
+        walls,
+        furnaces,
+        guards,
+        paradox: ParadoxRule::default(),
+        max_ghosts: DEFAULT_MAX_GHOSTS,
+        alarm_bodies: DEFAULT_ALARM_BODIES,
+        doors: vec![],
+        switches: vec![],
+        items: vec![],
+    }
+}
+
+/// Time a closed loop through `points` so it takes exactly `max_time`,
+/// starting `offset` seconds into the loop.
+/// The result is sorted by time, as [`sample_path`](super::movement::sample_path) expects.
+fn loop_path(points: &[Vec3], max_time: f32, offset: f32) -> Vec<(f32, Vec3)> {
+    let segment_lengths: Vec<f32> = points
+        .iter()
+        .zip(points.iter().cycle().skip(1))
+        .map(|(a, b)| a.distance(*b))
+        .collect();
+    let total: f32 = segment_lengths.iter().sum();
+    let mut elapsed = 0.0;
+    let mut path: Vec<(f32, Vec3)> = points
+        .iter()
+        .zip(&segment_lengths)
+        .map(|(&point, &length)| {
+            let time = (offset + elapsed / total * max_time) % max_time;
+            elapsed += length;
+            (time, point)
+        })
+        .collect();
+    // The loop is closed, so rotating it to start at the earliest waypoint keeps the route.
+    let first = path
+        .iter()
+        .enumerate()
+        .min_by(|(_, a), (_, b)| a.0.total_cmp(&b.0))
+        .map_or(0, |(index, _)| index);
+    path.rotate_left(first);
+    path
+}
+
+fn room_corner(room: IVec2) -> Vec3 {
+    Vec3::new(room.x as f32 * ROOM, 0.0, room.y as f32 * ROOM)
+}
+
+fn room_center(room: IVec2) -> Vec3 {
+    room_corner(room) + Vec3::new(ROOM / 2.0, 0.0, ROOM / 2.0)
+}
+
+/// Which rooms are connected by a door, and where that door is.
+struct Layout {
+    size: IVec2,
+    /// Door position along the wall on the +x side of each room, if there is one.
+    doors_x: Vec<Option<f32>>,
+    /// Door position along the wall on the +z side of each room, if there is one.
+    doors_z: Vec<Option<f32>>,
+}
+
+impl Layout {
+    fn generate(rng: &mut ChaCha8Rng, width: i32, height: i32) -> Self {
+        let size = IVec2::new(width, height);
+        let cells = (width * height) as usize;
+        let mut layout = Layout {
+            size,
+            doors_x: vec![None; cells],
+            doors_z: vec![None; cells],
+        };
+
+        // Randomized depth first search, which visits (and opens a door to) every room.
+        let mut visited = vec![false; cells];
+        let mut stack = vec![IVec2::ZERO];
+        visited[0] = true;
+        while let Some(&room) = stack.last() {
+            let unvisited: Vec<IVec2> = [IVec2::X, IVec2::NEG_X, IVec2::Y, IVec2::NEG_Y]
+                .into_iter()
+                .map(|step| room + step)
+                .filter(|&other| layout.contains(other) && !visited[layout.index(other)])
+                .collect();
+            match unvisited.choose(rng) {
+                Some(&other) => {
+                    layout.open(rng, room, other);
+                    visited[layout.index(other)] = true;
+                    stack.push(other);
+                }
+                None => {
+                    stack.pop();
+                }
+            }
+        }
+
+        for room in layout.rooms().collect::<Vec<_>>() {
+            for other in [room + IVec2::X, room + IVec2::Y] {
+                if layout.contains(other) && rng.gen_bool(EXTRA_DOOR_CHANCE) {
+                    layout.open(rng, room, other);
+                }
+            }
+        }
+        layout
+    }
+
+    fn contains(&self, room: IVec2) -> bool {
+        room.cmpge(IVec2::ZERO).all() && room.cmplt(self.size).all()
+    }
+
+    fn index(&self, room: IVec2) -> usize {
+        (room.y * self.size.x + room.x) as usize
+    }
+
+    fn rooms(&self) -> impl Iterator<Item = IVec2> + '_ {
+        (0..self.size.y).flat_map(move |y| (0..self.size.x).map(move |x| IVec2::new(x, y)))
+    }
+
+    /// Put a door between two neighbouring rooms.
+    fn open(&mut self, rng: &mut ChaCha8Rng, a: IVec2, b: IVec2) {
+        let (low, high) = if a.x + a.y < b.x + b.y { (a, b) } else { (b, a) };
+        let index = self.index(low);
+        let along = rng.gen_range(DOOR..ROOM - DOOR);
+        if high.x > low.x {
+            self.doors_x[index].get_or_insert(along);
+        } else {
+            self.doors_z[index].get_or_insert(along);
+        }
+    }
+
+    /// Rooms sharing a door with `room`, and the position of that door.
+    fn neighbours(&self, room: IVec2) -> impl Iterator<Item = (IVec2, Vec3)> + '_ {
+        let corner = room_corner(room);
+        let lower = |step: IVec2| room - step;
+        [
+            (room + IVec2::X, self.door_x(room).map(|z| corner + Vec3::new(ROOM, 0.0, z))),
+            (room + IVec2::Y, self.door_z(room).map(|x| corner + Vec3::new(x, 0.0, ROOM))),
+            (lower(IVec2::X), self.door_x(lower(IVec2::X)).map(|z| corner + Vec3::new(0.0, 0.0, z))),
+            (lower(IVec2::Y), self.door_z(lower(IVec2::Y)).map(|x| corner + Vec3::new(x, 0.0, 0.0))),
+        ]
+        .into_iter()
+        .filter_map(|(other, door)| Some((other, door?)))
+    }
+
+    fn door_x(&self, room: IVec2) -> Option<f32> {
+        self.contains(room).then(|| self.doors_x[self.index(room)]).flatten()
+    }
+
+    fn door_z(&self, room: IVec2) -> Option<f32> {
+        self.contains(room).then(|| self.doors_z[self.index(room)]).flatten()
+    }
+
+    fn walls(&self) -> Vec<WallData> {
+        let extent = room_corner(self.size);
+        let mut walls = vec![
+            // Outer walls.
+            wall_x(0.0, 0.0, extent.z),
+            wall_x(extent.x, 0.0, extent.z),
+            wall_z(0.0, 0.0, extent.x),
+            wall_z(extent.z, 0.0, extent.x),
+        ];
+        for room in self.rooms() {
+            let corner = room_corner(room);
+            if room.x + 1 < self.size.x {
+                let x = corner.x + ROOM;
+                match self.door_x(room) {
+                    Some(door) => {
+                        walls.push(wall_x(x, corner.z, corner.z + door - DOOR / 2.0));
+                        walls.push(wall_x(x, corner.z + door + DOOR / 2.0, corner.z + ROOM));
+                    }
+                    None => walls.push(wall_x(x, corner.z, corner.z + ROOM)),
+                }
+            }
+            if room.y + 1 < self.size.y {
+                let z = corner.z + ROOM;
+                match self.door_z(room) {
+                    Some(door) => {
+                        walls.push(wall_z(z, corner.x, corner.x + door - DOOR / 2.0));
+                        walls.push(wall_z(z, corner.x + door + DOOR / 2.0, corner.x + ROOM));
+                    }
+                    None => walls.push(wall_z(z, corner.x, corner.x + ROOM)),
+                }
+            }
+        }
+        walls
+    }
+}
+
+/// A wall along the z axis at `x`, from `z0` to `z1`.
+fn wall_x(x: f32, z0: f32, z1: f32) -> WallData {
+    WallData {
+        translation: Vec3::new(x, 2.0, (z0 + z1) / 2.0),
+        scale: Vec3::new(THICKNESS, WALL_HEIGHT, (z1 - z0) / 2.0 + THICKNESS),
+        rotation: 0.0,
+        shape: WallShape::Box,
+    }
+}
+
+/// A wall along the x axis at `z`, from `x0` to `x1`.
+fn wall_z(z: f32, x0: f32, x1: f32) -> WallData {
+    WallData {
+        translation: Vec3::new((x0 + x1) / 2.0, 2.0, z),
+        scale: Vec3::new((x1 - x0) / 2.0 + THICKNESS, WALL_HEIGHT, THICKNESS),
+        rotation: 0.0,
+        shape: WallShape::Box,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::validate::validate_level;
+
+    #[test]
+    fn same_seed_same_level() {
+        for seed in 0..20 {
+            assert_eq!(generate_level(seed), generate_level(seed), "seed {seed}");
+        }
+    }
+
+    #[test]
+    fn generated_levels_pass_validation() {
+        for seed in 0..100 {
+            let issues = validate_level(&generate_level(seed));
+            assert!(issues.is_empty(), "seed {seed}: {issues:?}");
+        }
+    }
+
+    /// Seeds are shared and stored in saves and replays, so a layout must not
+    /// change under them. If this fails, the generator or its RNG changed.
+    #[test]
+    fn seed_layout_is_pinned() {
+        let level = generate_level(42);
+        assert_eq!(level.max_time, 40.0);
+        assert_eq!(level.walls.len(), 36);
+        assert_eq!(level.furnaces[0].translation, Vec3::new(8.0, 1.0, 56.0));
+        assert_eq!(level.guards.len(), 5);
+    }
+
+    #[test]
+    fn different_seeds_different_levels() {
+        assert_ne!(generate_level(1), generate_level(2));
+    }
+
+    #[test]
+    fn first_guard_can_be_killed() {
+        for seed in 0..100 {
+            assert!(generate_level(seed).guards[0].kind.can_be_killed(), "seed {seed}");
+        }
+    }
+}
@@ -0,0 +1,128 @@
+//! Deciding which ghosts stay around.
+//! Every loop adds a ghost, but a level only has room for
+//! [`Level::max_ghosts`](super::level::Level) of them. When there are too many,
+//! the oldest one that isn't [`Locked`] is erased, together with its recording.
+//! Guards it killed stay dead, but nobody expects it back to kill them anymore.
+//! Players can also erase or lock a generation themselves with a [`GhostOrder`].
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    movement::{loop_time, Ghost},
+    paradox::KilledBy,
+    recording::GhostRecording,
+};
+use crate::{screen::InGame, FixedSet};
+
+pub(super) fn plugin(app: &mut App) {
+    app.register_type::<GhostLimit>();
+    app.init_resource::<GhostLimit>();
+    app.register_type::<Locked>();
+
+    app.observe(apply_ghost_order);
+    app.add_systems(
+        FixedUpdate,
+        enforce_ghost_limit
+            .after(loop_time)
+            .in_set(FixedSet::Tick)
+            .run_if(in_state(InGame)),
+    );
+}
+
+/// How many ghosts can exist at once.
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq, Reflect)]
+#[reflect(Resource)]
+pub struct GhostLimit(pub usize);
+
+impl Default for GhostLimit {
+    fn default() -> Self {
+        Self(DEFAULT_MAX_GHOSTS)
+    }
+}
+
+pub const DEFAULT_MAX_GHOSTS: usize = 4;
+
+/// A ghost that is never erased to make room for a newer one.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Default, Reflect)]
+#[reflect(Component)]
+pub struct Locked;
+
+/// Something the player decided about a generation of ghosts.
+/// These are recorded in replays, since they change how the run plays out.
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq, Reflect, Serialize, Deserialize)]
+pub enum GhostOrder {
+    Erase { gen: u16 },
+    ToggleLock { gen: u16 },
+}
+
+fn apply_ghost_order(
+    trigger: Trigger<GhostOrder>,
+    ghosts: Query<(Entity, &Ghost, Has<Locked>)>,
+    killed: Query<(Entity, &KilledBy)>,
+    mut recording: ResMut<GhostRecording>,
+    mut commands: Commands,
+) {
+    let order = *trigger.event();
+    let gen = match order {
+        GhostOrder::Erase { gen } | GhostOrder::ToggleLock { gen } => gen,
+    };
+    let Some((entity, _, locked)) = ghosts.iter().find(|(_, ghost, _)| ghost.gen == gen) else {
+        return;
+    };
+    match order {
+        GhostOrder::Erase { .. } => {
+            erase_ghost(&mut commands, &mut recording, &killed, entity, gen);
+        }
+        GhostOrder::ToggleLock { .. } if locked => {
+            commands.entity(entity).remove::<Locked>();
+        }
+        GhostOrder::ToggleLock { .. } => {
+            commands.entity(entity).insert(Locked);
+        }
+    }
+}
+
+/// Drop the oldest unlocked ghosts until there is room again.
+/// If they are all locked, that includes the one that was just created.
+fn enforce_ghost_limit(
+    limit: Res<GhostLimit>,
+    ghosts: Query<(Entity, &Ghost, Has<Locked>)>,
+    killed: Query<(Entity, &KilledBy)>,
+    mut recording: ResMut<GhostRecording>,
+    mut commands: Commands,
+) {
+    let excess = ghosts.iter().count().saturating_sub(limit.0);
+    if excess == 0 {
+        return;
+    }
+    let mut unlocked: Vec<_> = ghosts
+        .iter()
+        .filter(|(_, _, locked)| !locked)
+        .map(|(entity, ghost, _)| (ghost.gen, entity))
+        .collect();
+    unlocked.sort();
+    for (gen, entity) in unlocked.into_iter().take(excess) {
+        erase_ghost(&mut commands, &mut recording, &killed, entity, gen);
+    }
+}
+
+/// Remove a ghost for good. Its recording goes too, since nothing can replay it anymore,
+/// and so do its kills, or every later loop would miss it at the bodies.
+fn erase_ghost(
+    commands: &mut Commands,
+    recording: &mut GhostRecording,
+    killed: &Query<(Entity, &KilledBy)>,
+    entity: Entity,
+    gen: u16,
+) {
+    commands.entity(entity).despawn_recursive();
+    for (guard, killed_by) in killed {
+        if killed_by.gen == gen {
+            commands.entity(guard).remove::<KilledBy>();
+        }
+    }
+    if let Some(erased) = recording.loops.get_mut(usize::from(gen)) {
+        erased.clear();
+    }
+}
@@ -0,0 +1,613 @@
+//! What guards do, from walking their rounds to shooting on sight.
+//! Seeing a player or a ghost fills a guard's detection meter, faster the
+//! closer and better lit they are, and it drains again once they are out of
+//! sight. Guards see further into light than into the dark. Only a
+//! full meter gets anyone shot, so breaking line of sight in time gets
+//! away. Each guard runs a small [`GuardState`] machine on top of that and
+//! goes back to its [`Path`] schedule once it calms down. Off its path, a
+//! guard finds its way around walls with the [`NavGrid`].
+//! Levels pick a [`GuardKind`] for each guard, and its [`Profile`] says how
+//! it sees, how fast it walks, how it deals with whoever it spots and how it
+//! can be killed.
+//! Guards also notice the bodies of other guards. Finding one alerts the
+//! guard, calls the guards around it over, and enough of them raise an
+//! [`Alarm`] that sends every guard searching and makes them quicker to spot
+//! anyone for the rest of the loop.
+
+use std::f32::consts::PI;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    assets::{Action, NlaTrack},
+    collision::{move_and_slide, CHARACTER_RADIUS},
+    movement::{
+        furnaceloop, sample_path, Ghost, IsDead, IsGoingToHell, Npc, Path, Timeloop,
+    },
+    navigation::{DistanceField, NavGrid},
+    noise::{radius_towards, Noise},
+    spatial::{CharacterIndex, WallIndex},
+    spawn::{player::Player, stage::Furnace},
+    vision::{
+        can_see, exposure, light_fill_factor, lights, sight_range, VIEW_DISTANCE, VIEW_HALF_ANGLE,
+    },
+    world::LoopStarted,
+};
+use crate::{screen::InGame, FixedSet};
+
+pub(super) fn plugin(app: &mut App) {
+    app.register_type::<GuardKind>();
+    app.register_type::<GuardAi>();
+    app.register_type::<Discovered>();
+    app.register_type::<Alarm>();
+    app.init_resource::<Alarm>();
+
+    app.observe(reset_alarm);
+    app.add_systems(
+        FixedUpdate,
+        (
+            move_guards.in_set(FixedSet::Move),
+            (detect_player, discover_bodies)
+                .chain()
+                .after(furnaceloop)
+                .in_set(FixedSet::React),
+        )
+            .run_if(in_state(InGame)),
+    );
+}
+
+/// Seconds to fill the meter when someone stands right in front of a guard.
+const SPOT_TIME: f32 = 0.3;
+/// Extra seconds to fill the meter for every unit further away.
+const SPOT_TIME_PER_UNIT: f32 = 0.08;
+/// How much of the meter empties every second nobody is in sight.
+const DRAIN_RATE: f32 = 0.5;
+/// How full the meter gets before a guard stops to look.
+const SUSPICIOUS_LEVEL: f32 = 0.2;
+/// How long a guard searches before giving up, walk there included.
+const INVESTIGATE_TIME: f32 = 6.0;
+/// How long a guard walks back before giving up and picking its path up where it is by now,
+/// in case a closed door cut it off.
+const RETURN_TIME: f32 = 10.0;
+/// Walking speed of guards off their path, a bit quicker than on it.
+const GUARD_SPEED: f32 = 5.0;
+/// How close a hound has to get to bite.
+const BITE_RANGE: f32 = 1.2;
+/// How far a camera's report carries, walls muffle it like a noise.
+const REPORT_RADIUS: f32 = 25.0;
+/// How close counts as having arrived somewhere.
+const ARRIVE_DISTANCE: f32 = 0.5;
+/// How fast a guard turns while looking around, in radians per second.
+const LOOK_AROUND_SPEED: f32 = 1.5;
+/// How far a guard that found a body calls for the others, walls muffle it like a noise.
+const CALL_RADIUS: f32 = 15.0;
+/// How much faster the meter fills while the alarm is raised.
+const ALARM_FILL_FACTOR: f32 = 2.0;
+pub const DEFAULT_ALARM_BODIES: u32 = 2;
+
+/// The kinds of guards a level can have.
+#[derive(
+    Component, Debug, Clone, Copy, PartialEq, Eq, Default, Reflect, Serialize, Deserialize,
+)]
+#[reflect(Component)]
+pub enum GuardKind {
+    #[default]
+    Guard,
+    /// Sees far down a narrow cone.
+    Sniper,
+    /// Short-sighted, but follows the scent people leave, see
+    /// [`Scent`](super::scent::Scent), and bites.
+    Hound,
+    /// Stays at its first waypoint and turns to look at the others on their
+    /// schedule. Reports whoever it spots to the guards around, and can't be
+    /// destroyed.
+    Camera,
+    /// Slow, and only killed from behind.
+    Heavy,
+}
+
+/// How a [`GuardKind`] sees, moves and fights.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Profile {
+    pub view_half_angle: f32,
+    /// How far it sees someone in full light.
+    pub view_distance: f32,
+    /// How much faster than a regular guard it fills its meter.
+    pub alertness: f32,
+    /// Walking speed off its path, 0 for guards that never leave their post.
+    pub speed: f32,
+    pub attack: Attack,
+    pub kill_rule: KillRule,
+    pub tracks_scent: bool,
+    /// Size of its model.
+    pub scale: f32,
+}
+
+/// What a guard does to whoever it is sure about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Attack {
+    Shoot,
+    /// Runs at them and kills them up close.
+    Bite,
+    /// Calls the guards around to where they are.
+    Report,
+}
+
+/// How the player and ghosts can kill a guard, by getting close to it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KillRule {
+    Anywhere,
+    /// Only from the half of the floor behind it.
+    FromBehind,
+    Never,
+}
+
+impl GuardKind {
+    pub fn profile(self) -> Profile {
+        let guard = Profile {
+            view_half_angle: VIEW_HALF_ANGLE,
+            view_distance: VIEW_DISTANCE,
+            alertness: 1.0,
+            speed: GUARD_SPEED,
+            attack: Attack::Shoot,
+            kill_rule: KillRule::Anywhere,
+            tracks_scent: false,
+            scale: 1.0,
+        };
+        match self {
+            GuardKind::Guard => guard,
+            GuardKind::Sniper => Profile {
+                view_half_angle: PI / 14.0,
+                view_distance: 2.0 * VIEW_DISTANCE,
+                // Makes up for how slowly the meter fills far away.
+                alertness: 1.5,
+                speed: 4.0,
+                ..guard
+            },
+            GuardKind::Hound => Profile {
+                view_half_angle: PI / 3.0,
+                view_distance: 0.4 * VIEW_DISTANCE,
+                alertness: 1.2,
+                speed: 7.0,
+                attack: Attack::Bite,
+                tracks_scent: true,
+                scale: 0.7,
+                ..guard
+            },
+            GuardKind::Camera => Profile {
+                view_half_angle: PI / 6.0,
+                view_distance: 1.25 * VIEW_DISTANCE,
+                speed: 0.0,
+                attack: Attack::Report,
+                kill_rule: KillRule::Never,
+                ..guard
+            },
+            GuardKind::Heavy => Profile {
+                alertness: 0.8,
+                speed: 3.5,
+                kill_rule: KillRule::FromBehind,
+                scale: 1.3,
+                ..guard
+            },
+        }
+    }
+
+    /// Whether anyone can kill it. Levels are won once all of these are dead.
+    pub fn can_be_killed(self) -> bool {
+        self.profile().kill_rule != KillRule::Never
+    }
+}
+
+impl KillRule {
+    /// Whether someone at `killer` can kill the guard standing at `guard`.
+    pub fn allows(self, guard: &Transform, killer: Vec3) -> bool {
+        match self {
+            KillRule::Anywhere => true,
+            KillRule::FromBehind => {
+                (killer - guard.translation).xz().dot(guard.forward().xz()) < 0.0
+            }
+            KillRule::Never => false,
+        }
+    }
+}
+
+/// How worried a guard is, and what it does about it.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Default, Reflect)]
+#[reflect(Component)]
+pub struct GuardAi {
+    pub state: GuardState,
+    /// Fills from 0 to 1 while the guard sees someone. Full means spotted.
+    pub detection: f32,
+    /// Where the guard last saw someone, or heard something.
+    pub last_seen: Vec3,
+    /// Seconds spent in the current state.
+    pub timer: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Reflect)]
+pub enum GuardState {
+    /// Following its [`Path`] on schedule.
+    #[default]
+    Patrol,
+    /// Saw something, stops and stares at it.
+    Suspicious,
+    /// Lost sight of it, walks to where it was and looks around.
+    Investigate,
+    /// Saw someone for sure, shoots whoever stays in sight.
+    Alert,
+    /// Calmed down, walks back to where its path is by now.
+    Return,
+}
+
+impl GuardAi {
+    pub fn set_state(&mut self, state: GuardState) {
+        if self.state != state {
+            self.state = state;
+            self.timer = 0.0;
+        }
+    }
+
+    /// Go and look at something heard at `position`, unless busy with someone seen.
+    pub fn hear(&mut self, position: Vec3) {
+        if matches!(self.state, GuardState::Suspicious | GuardState::Alert) {
+            return;
+        }
+        self.last_seen = position;
+        // Start over, even if it was already looking into something else.
+        self.state = GuardState::Investigate;
+        self.timer = 0.0;
+    }
+
+    /// Pick the next state from the meter, after it was updated for this tick.
+    fn think(&mut self) {
+        let next = match self.state {
+            _ if self.detection >= 1.0 => GuardState::Alert,
+            GuardState::Alert | GuardState::Suspicious if self.detection > 0.0 => self.state,
+            GuardState::Alert | GuardState::Suspicious => GuardState::Investigate,
+            _ if self.detection >= SUSPICIOUS_LEVEL => GuardState::Suspicious,
+            GuardState::Investigate if self.timer > INVESTIGATE_TIME => GuardState::Return,
+            GuardState::Return if self.timer > RETURN_TIME => GuardState::Patrol,
+            state => state,
+        };
+        self.set_state(next);
+    }
+}
+
+/// A dead guard someone already found.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Default, Reflect)]
+#[reflect(Component)]
+pub struct Discovered;
+
+/// How many bodies guards found this loop, and whether that was enough to raise the alarm.
+/// Like everything else the loop resets, it starts over with each loop.
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq, Default, Reflect)]
+#[reflect(Resource)]
+pub struct Alarm {
+    /// Bodies it takes to raise it, 0 for never.
+    pub threshold: u32,
+    pub bodies_found: u32,
+    pub raised: bool,
+}
+
+impl Alarm {
+    pub fn new(threshold: u32) -> Self {
+        Self {
+            threshold,
+            bodies_found: 0,
+            raised: false,
+        }
+    }
+}
+
+/// How much of the meter someone fills per second at `distance`.
+fn fill_rate(distance: f32) -> f32 {
+    1.0 / (SPOT_TIME + distance * SPOT_TIME_PER_UNIT)
+}
+
+/// The way to wherever a guard is walking off its path, searched again only once
+/// that place or the doors change.
+#[derive(Component, Debug, Clone, Default)]
+pub struct Route(DistanceField);
+
+/// Step towards `target` around walls.
+/// Returns whether there is still some way to go.
+/// The route is followed from wherever the guard stands, so it only depends on that.
+fn walk_towards(
+    transform: &mut Transform,
+    target: Vec3,
+    speed: f32,
+    grid: &NavGrid,
+    walls: &WallIndex,
+    route: &mut Route,
+    delta: f32,
+) -> bool {
+    let position = transform.translation.xz();
+    if position.distance(target.xz()) < ARRIVE_DISTANCE {
+        return false;
+    }
+    let corner = if grid.is_clear(position, target.xz()) {
+        target.xz()
+    } else {
+        if !grid.is_current(&route.0, target.xz()) {
+            route.0 = grid.distance_field(target.xz());
+        }
+        // Without a route, head straight for it and slide along whatever is in the way.
+        grid.follow(&route.0, position, target.xz())
+            .first()
+            .copied()
+            .unwrap_or(target.xz())
+    };
+    let motion = (corner - position).clamp_length_max(speed * delta);
+    let obstacles = walls.near(position, motion.length() + 2.0 * CHARACTER_RADIUS);
+    let moved = move_and_slide(position, motion, CHARACTER_RADIUS, &obstacles);
+    transform.translation = Vec3::new(moved.x, transform.translation.y, moved.y);
+    face(transform, Vec3::new(corner.x, transform.translation.y, corner.y));
+    true
+}
+
+/// Where a camera stands and looks at `time`, see [`GuardKind::Camera`].
+pub fn camera_pose(points: &[(f32, Vec3)], time: f32, max_time: f32) -> Transform {
+    let Some(&(_, position)) = points.first() else {
+        return Transform::IDENTITY;
+    };
+    let target = sample_path(&points[1..], time, max_time)
+        .map_or(position + Vec3::NEG_Z, |(target, _)| target);
+    let mut transform = Transform::from_translation(position);
+    face(&mut transform, target);
+    transform
+}
+
+fn face(transform: &mut Transform, target: Vec3) {
+    let direction = (target - transform.translation).with_y(0.0);
+    if direction.length_squared() > 0.0 {
+        *transform = transform.looking_to(direction, Vec3::Y);
+    }
+}
+
+/// Guards on patrol keep to their schedule, the others go wherever their state takes them.
+pub fn move_guards(
+    time: Res<Time>,
+    timeloop: Res<Timeloop>,
+    grid: Res<NavGrid>,
+    mut guards: Query<
+        (&Path, &GuardKind, &mut Transform, &mut Action, &mut GuardAi, &mut Route),
+        (With<Npc>, Without<IsDead>),
+    >,
+    walls: Res<WallIndex>,
+) {
+    let delta = time.delta_seconds();
+    for (path, kind, mut transform, mut action, mut ai, mut route) in &mut guards {
+        let profile = kind.profile();
+        let speed = profile.speed;
+        if speed <= 0.0 {
+            // Nowhere to walk to, so searching is just looking the usual way.
+            if matches!(ai.state, GuardState::Suspicious | GuardState::Alert) {
+                face(&mut transform, ai.last_seen);
+            } else {
+                let pose = camera_pose(&path.points, timeloop.current_time(), timeloop.max_time());
+                transform.translation = pose.translation;
+                transform.rotation = pose.rotation;
+                ai.set_state(GuardState::Patrol);
+            }
+            continue;
+        }
+        let Some((on_path, direction)) =
+            sample_path(&path.points, timeloop.current_time(), timeloop.max_time())
+        else {
+            continue;
+        };
+        match ai.state {
+            GuardState::Patrol => {
+                transform.translation = on_path;
+                *transform = transform.looking_to(direction, Vec3::Y);
+                action.new_track = NlaTrack::Walk;
+            }
+            GuardState::Alert if profile.attack == Attack::Bite => {
+                let target = ai.last_seen;
+                walk_towards(&mut transform, target, speed, &grid, &walls, &mut route, delta);
+                action.new_track = NlaTrack::Walk;
+            }
+            GuardState::Suspicious | GuardState::Alert => {
+                face(&mut transform, ai.last_seen);
+                if action.new_track != NlaTrack::Shoot {
+                    action.new_track = NlaTrack::Idle;
+                }
+            }
+            GuardState::Investigate => {
+                let target = ai.last_seen;
+                if walk_towards(&mut transform, target, speed, &grid, &walls, &mut route, delta) {
+                    action.new_track = NlaTrack::Walk;
+                } else {
+                    transform.rotate_y(LOOK_AROUND_SPEED * delta);
+                    action.new_track = NlaTrack::Idle;
+                }
+            }
+            GuardState::Return => {
+                if walk_towards(&mut transform, on_path, speed, &grid, &walls, &mut route, delta) {
+                    action.new_track = NlaTrack::Walk;
+                } else {
+                    ai.set_state(GuardState::Patrol);
+                }
+            }
+        }
+    }
+}
+
+/// Guards watch for the player and ghosts, and [`Attack`] them once they are sure.
+/// Killing the player sends them to hell, killing a ghost just kills it.
+pub fn detect_player(
+    time: Res<Time>,
+    alarm: Res<Alarm>,
+    mut targets: Query<
+        (&Transform, Entity, &mut Action, Has<Player>),
+        (Or<(With<Player>, With<Ghost>)>, Without<IsGoingToHell>, Without<Npc>, Without<IsDead>),
+    >,
+    mut guards: Query<
+        (&mut Transform, &GuardKind, &mut Action, &mut GuardAi),
+        (With<Npc>, Without<IsDead>, Without<Player>, Without<Ghost>),
+    >,
+    walls: Res<WallIndex>,
+    characters: Res<CharacterIndex>,
+    furnaces: Query<(&Transform, &Furnace), Without<Npc>>,
+    lamps: Query<(Entity, &Transform), (With<Player>, Without<Npc>)>,
+    mut commands: Commands,
+) {
+    let lights = lights(furnaces.iter(), lamps.iter());
+    let exposures: Vec<(Entity, f32)> = targets
+        .iter()
+        .map(|(target, target_id, ..)| {
+            (target_id, exposure(target.translation, Some(target_id), &lights, &walls))
+        })
+        .collect();
+    let delta = time.delta_seconds();
+    let mut shot = vec![];
+    for (mut guard, kind, mut action, mut ai) in &mut guards {
+        let profile = kind.profile();
+        let nearby: Vec<Entity> = characters
+            .near(guard.translation, profile.view_distance)
+            .map(|(entity, _)| entity)
+            .collect();
+        // Whoever is easiest to spot is the one that counts.
+        let seen = exposures
+            .iter()
+            .filter(|(target_id, _)| nearby.contains(target_id))
+            .filter_map(|&(target_id, exposure)| {
+                let (target, ..) = targets.get(target_id).ok()?;
+                let range = sight_range(profile.view_distance, exposure);
+                let visible = !shot.contains(&target_id)
+                    && can_see(
+                        &guard,
+                        target.translation,
+                        profile.view_half_angle,
+                        range,
+                        &walls.along(guard.translation.xz(), target.translation.xz()),
+                    );
+                visible.then_some((target_id, target.translation, exposure))
+            })
+            .map(|(target_id, position, exposure)| {
+                let mut rate = fill_rate(guard.translation.distance(position))
+                    * light_fill_factor(exposure)
+                    * profile.alertness;
+                if alarm.raised {
+                    rate *= ALARM_FILL_FACTOR;
+                }
+                (target_id, position, rate)
+            })
+            .max_by(|a, b| a.2.total_cmp(&b.2));
+
+        ai.timer += delta;
+        match seen {
+            Some((_, position, rate)) => {
+                ai.detection = (ai.detection + rate * delta).min(1.0);
+                ai.last_seen = position;
+            }
+            None => ai.detection = (ai.detection - DRAIN_RATE * delta).max(0.0),
+        }
+        let was_alert = ai.state == GuardState::Alert;
+        ai.think();
+
+        match (ai.state, seen, profile.attack) {
+            (GuardState::Alert, Some((target_id, position, _)), Attack::Shoot) => {
+                face(&mut guard, position);
+                action.new_track = NlaTrack::Shoot;
+                shot.push(target_id);
+            }
+            (GuardState::Alert, Some((target_id, position, _)), Attack::Bite)
+                if guard.translation.xz().distance(position.xz()) < BITE_RANGE =>
+            {
+                shot.push(target_id);
+            }
+            (GuardState::Alert, Some((_, position, _)), Attack::Report) if !was_alert => {
+                commands.trigger(Noise {
+                    position,
+                    radius: REPORT_RADIUS,
+                });
+            }
+            _ => {}
+        }
+    }
+    for &target_id in &shot {
+        let Ok((_, _, mut action, is_player)) = targets.get_mut(target_id) else {
+            continue;
+        };
+        commands.entity(target_id).insert(IsDead);
+        if is_player {
+            commands.entity(target_id).insert(IsGoingToHell { countdown: 0.7 });
+        } else {
+            action.new_track = NlaTrack::Die;
+        }
+    }
+}
+
+/// Guards that see a body nobody found yet are alerted and call the others over.
+fn discover_bodies(
+    mut guards: Query<(Entity, &Transform, &GuardKind, &mut GuardAi), (With<Npc>, Without<IsDead>)>,
+    bodies: Query<(Entity, &Transform), (With<Npc>, With<IsDead>, Without<Discovered>)>,
+    walls: Res<WallIndex>,
+    furnaces: Query<(&Transform, &Furnace), Without<Npc>>,
+    lamps: Query<(Entity, &Transform), (With<Player>, Without<Npc>)>,
+    mut alarm: ResMut<Alarm>,
+    mut commands: Commands,
+) {
+    let lights = lights(furnaces.iter(), lamps.iter());
+    let mut found = vec![];
+    for (body, body_transform) in &bodies {
+        let position = body_transform.translation;
+        let exposure = exposure(position, None, &lights, &walls);
+        let finder = guards.iter().find(|(_, guard, kind, _)| {
+            let profile = kind.profile();
+            let range = sight_range(profile.view_distance, exposure);
+            let obstacles = walls.along(guard.translation.xz(), position.xz());
+            can_see(guard, position, profile.view_half_angle, range, &obstacles)
+        });
+        if let Some((finder, finder_transform, ..)) = finder {
+            found.push((finder, finder_transform.translation, body_transform.translation));
+            commands.entity(body).insert(Discovered);
+        }
+    }
+
+    for (finder, calling_from, body) in found {
+        info!("A guard found a body at {body}");
+        let call = Noise {
+            position: calling_from,
+            radius: CALL_RADIUS,
+        };
+        for (guard, transform, _, mut ai) in &mut guards {
+            if guard == finder {
+                ai.detection = 1.0;
+                ai.last_seen = body;
+                ai.set_state(GuardState::Alert);
+            } else if transform.translation.distance(calling_from)
+                < radius_towards(
+                    &call,
+                    transform.translation,
+                    &walls.along(calling_from.xz(), transform.translation.xz()),
+                )
+            {
+                ai.hear(body);
+            }
+        }
+        alarm.bodies_found += 1;
+        if alarm.threshold > 0 && alarm.bodies_found >= alarm.threshold && !alarm.raised {
+            info!("The alarm is raised");
+            alarm.raised = true;
+            for (.., mut ai) in &mut guards {
+                ai.hear(body);
+            }
+        }
+    }
+}
+
+/// Bodies can be found again in the next loop, and the alarm starts over.
+fn reset_alarm(
+    _trigger: Trigger<LoopStarted>,
+    mut alarm: ResMut<Alarm>,
+    discovered: Query<Entity, With<Discovered>>,
+    mut commands: Commands,
+) {
+    *alarm = Alarm::new(alarm.threshold);
+    for body in &discovered {
+        commands.entity(body).remove::<Discovered>();
+    }
+}
@@ -0,0 +1,62 @@
+//! Smooth rendering on top of the fixed timestep.
+//! Gameplay moves characters in `FixedUpdate`, which runs zero or more times
+//! per frame. Entities with [`Interpolated`] are drawn part of the way between
+//! their last two simulated transforms, depending on how far the frame is
+//! into the next tick, so they don't stutter when the frame rate and tick rate
+//! don't line up.
+
+use bevy::prelude::*;
+
+use crate::AppSet;
+
+pub(super) fn plugin(app: &mut App) {
+    app.register_type::<Interpolated>();
+    app.add_systems(FixedFirst, restore_simulated);
+    app.add_systems(FixedLast, record_simulated);
+    app.add_systems(Update, interpolate.in_set(AppSet::Update));
+}
+
+#[derive(Component, Debug, Clone, Copy, PartialEq, Reflect)]
+#[reflect(Component)]
+pub struct Interpolated {
+    pub previous: Transform,
+    pub current: Transform,
+}
+
+impl Interpolated {
+    /// At rest at `transform`. Insert this again after teleporting an entity,
+    /// so it doesn't visibly slide to its new position.
+    pub fn new(transform: Transform) -> Self {
+        Self {
+            previous: transform,
+            current: transform,
+        }
+    }
+}
+
+/// Undo the interpolation before gameplay runs, so it only ever sees simulated transforms.
+fn restore_simulated(mut query: Query<(&mut Transform, &mut Interpolated)>) {
+    for (mut transform, mut interpolated) in &mut query {
+        interpolated.previous = interpolated.current;
+        *transform = interpolated.current;
+    }
+}
+
+fn record_simulated(mut query: Query<(&Transform, &mut Interpolated)>) {
+    for (transform, mut interpolated) in &mut query {
+        interpolated.current = *transform;
+    }
+}
+
+pub(super) fn interpolate(
+    fixed_time: Res<Time<Fixed>>,
+    mut query: Query<(&mut Transform, &Interpolated)>,
+) {
+    let fraction = fixed_time.overstep_fraction();
+    for (mut transform, interpolated) in &mut query {
+        let (previous, current) = (interpolated.previous, interpolated.current);
+        transform.translation = previous.translation.lerp(current.translation, fraction);
+        transform.rotation = previous.rotation.slerp(current.rotation, fraction);
+        transform.scale = previous.scale.lerp(current.scale, fraction);
+    }
+}
@@ -0,0 +1,233 @@
+//! Serialized level layouts, loaded through the asset server.
+//! A level describes everything [`SpawnStage`](super::spawn::stage::SpawnStage)
+//! needs to build a stage, so maps can be changed without recompiling.
+
+use std::fmt;
+
+use bevy::{
+    asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext},
+    prelude::*,
+};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    collision::{Collider, Obstacle},
+    ghosts::DEFAULT_MAX_GHOSTS,
+    guards::{GuardKind, DEFAULT_ALARM_BODIES},
+    paradox::ParadoxRule,
+    world::Persistence,
+};
+
+pub(super) fn plugin(app: &mut App) {
+    app.init_asset::<Level>();
+    app.init_asset_loader::<LevelLoader>();
+}
+
+/// A complete stage layout.
+#[derive(Asset, TypePath, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Level {
+    pub name: String,
+    /// Length of one loop in seconds, see [`Timeloop::max_time`](super::movement::Timeloop).
+    pub max_time: f32,
+    pub player_start: Vec3,
+    pub walls: Vec<WallData>,
+    pub furnaces: Vec<FurnaceData>,
+    pub guards: Vec<GuardData>,
+    /// What happens when the present contradicts a ghost.
+    #[serde(default)]
+    pub paradox: ParadoxRule,
+    /// How many ghosts can be around at once, see [`GhostLimit`](super::ghosts::GhostLimit).
+    #[serde(default = "default_max_ghosts")]
+    pub max_ghosts: usize,
+    /// How many bodies guards find before raising the alarm, see [`Alarm`](super::guards::Alarm).
+    #[serde(default = "default_alarm_bodies")]
+    pub alarm_bodies: u32,
+    #[serde(default)]
+    pub doors: Vec<DoorData>,
+    #[serde(default)]
+    pub switches: Vec<SwitchData>,
+    #[serde(default)]
+    pub items: Vec<ItemData>,
+}
+
+fn default_max_ghosts() -> usize {
+    DEFAULT_MAX_GHOSTS
+}
+
+fn default_alarm_bodies() -> u32 {
+    DEFAULT_ALARM_BODIES
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WallData {
+    pub translation: Vec3,
+    /// Half extents of the wall, since the wall mesh is a cube of length 2.
+    /// Pillars use `x` as their radius, polygons only use `y`.
+    pub scale: Vec3,
+    /// Turn around the vertical axis, in radians.
+    #[serde(default)]
+    pub rotation: f32,
+    #[serde(default)]
+    pub shape: WallShape,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub enum WallShape {
+    #[default]
+    Box,
+    /// A round pillar.
+    Pillar,
+    /// A convex outline on the floor, relative to `translation`.
+    Polygon(Vec<Vec2>),
+}
+
+impl WallData {
+    pub fn collider(&self) -> Collider {
+        match &self.shape {
+            WallShape::Box => Collider::Cuboid {
+                half_size: self.scale.xz(),
+            },
+            WallShape::Pillar => Collider::Circle {
+                radius: self.scale.x,
+            },
+            WallShape::Polygon(points) => Collider::Polygon {
+                points: points.clone(),
+            },
+        }
+    }
+
+    /// Where this wall blocks movement and sight.
+    pub fn obstacle(&self) -> Obstacle {
+        Obstacle::new(
+            &self.collider(),
+            self.translation,
+            Quat::from_rotation_y(self.rotation),
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FurnaceData {
+    pub name: String,
+    pub translation: Vec3,
+}
+
+/// A box shaped wall that can be opened, see [`Door`](super::world::Door).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DoorData {
+    pub name: String,
+    pub translation: Vec3,
+    /// Half extents, like [`WallData::scale`].
+    pub scale: Vec3,
+    #[serde(default)]
+    pub rotation: f32,
+    /// Name of the item that opens it.
+    #[serde(default)]
+    pub key: Option<String>,
+    #[serde(default)]
+    pub persistence: Persistence,
+}
+
+impl DoorData {
+    pub fn collider(&self) -> Collider {
+        Collider::Cuboid {
+            half_size: self.scale.xz(),
+        }
+    }
+
+    /// Where this door blocks movement and sight while closed.
+    pub fn obstacle(&self) -> Obstacle {
+        Obstacle::new(
+            &self.collider(),
+            self.translation,
+            Quat::from_rotation_y(self.rotation),
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SwitchData {
+    pub name: String,
+    pub translation: Vec3,
+    /// Names of the doors it opens.
+    pub doors: Vec<String>,
+    #[serde(default)]
+    pub persistence: Persistence,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ItemData {
+    pub name: String,
+    pub translation: Vec3,
+    #[serde(default)]
+    pub persistence: Persistence,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GuardData {
+    pub name: String,
+    #[serde(default)]
+    pub kind: GuardKind,
+    /// Waypoints as `(time, position)`, see [`Path`](super::movement::Path).
+    pub path: Vec<(f32, Vec3)>,
+}
+
+impl Level {
+    /// Parse a level from its RON representation, without going through the asset server.
+    pub fn from_ron(text: &str) -> Result<Self, LevelLoaderError> {
+        Ok(ron::de::from_str(text)?)
+    }
+}
+
+#[derive(Default)]
+pub struct LevelLoader;
+
+#[derive(Debug)]
+pub enum LevelLoaderError {
+    Io(std::io::Error),
+    Ron(ron::error::SpannedError),
+}
+
+impl fmt::Display for LevelLoaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LevelLoaderError::Io(error) => write!(f, "could not read level: {error}"),
+            LevelLoaderError::Ron(error) => write!(f, "could not parse level: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for LevelLoaderError {}
+
+impl From<std::io::Error> for LevelLoaderError {
+    fn from(error: std::io::Error) -> Self {
+        LevelLoaderError::Io(error)
+    }
+}
+
+impl From<ron::error::SpannedError> for LevelLoaderError {
+    fn from(error: ron::error::SpannedError) -> Self {
+        LevelLoaderError::Ron(error)
+    }
+}
+
+impl AssetLoader for LevelLoader {
+    type Asset = Level;
+    type Settings = ();
+    type Error = LevelLoaderError;
+
+    async fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader<'_>,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext<'_>,
+    ) -> Result<Level, LevelLoaderError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        Ok(ron::de::from_bytes(&bytes)?)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["level.ron"]
+    }
+}
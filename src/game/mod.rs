@@ -5,15 +5,55 @@ use bevy::prelude::*;
 mod animation;
 pub mod assets;
 pub mod audio;
+pub mod campaign;
+pub mod collision;
+pub mod editor;
+pub mod generator;
+pub mod ghosts;
+pub mod guards;
+pub mod interpolation;
+pub mod level;
 pub mod movement;
+pub mod navigation;
+pub mod noise;
+pub mod paradox;
+pub mod recording;
+pub mod replay;
+pub mod rewind;
+pub mod save;
+pub mod scent;
+pub mod spatial;
 pub mod spawn;
+pub mod validate;
+pub mod vision;
+pub mod world;
 
 pub(super) fn plugin(app: &mut App) {
     app.add_plugins((
 //        animation::plugin,
         audio::plugin,
         assets::plugin,
+        level::plugin,
+        campaign::plugin,
+        collision::plugin,
+        editor::plugin,
+        ghosts::plugin,
+        guards::plugin,
+        interpolation::plugin,
         movement::plugin,
+        navigation::plugin,
+        paradox::plugin,
+        recording::plugin,
+        replay::plugin,
+        rewind::plugin,
+    ));
+    app.add_plugins((
+        noise::plugin,
+        save::plugin,
+        scent::plugin,
+        spatial::plugin,
         spawn::plugin,
+        vision::plugin,
+        world::plugin,
     ));
 }
@@ -1,10 +1,11 @@
 //! Handle player input and translate it into movement.
-//! Note that the approach used here is simple for demonstration purposes.
-//! If you want to move the player in a smoother way,
-//! consider using a [fixed timestep](https://github.com/bevyengine/bevy/blob/latest/examples/movement/physics_in_fixed_timestep.rs).
+//! Input is recorded every frame, but everything it affects runs on a
+//! [fixed timestep](https://github.com/bevyengine/bevy/blob/latest/examples/movement/physics_in_fixed_timestep.rs)
+//! of [`TICK_RATE`] ticks per second, so the same inputs give the same loop at any frame rate.
+//! See [`interpolation`](super::interpolation) for how that still renders smoothly.
 
 use core::f32;
-use std::{f32::consts::PI, time::Duration};
+use std::time::Duration;
 
 use bevy::prelude::*;
 
@@ -13,12 +14,20 @@ use crate::{
         spawn::player::Player,
         spawn::stage::Wall,
     },
-    screen::Screen,
+    screen::InGame,
     
 };
-use crate::AppSet;
+use crate::{AppSet, FixedSet};
+
+use super::{assets::{Action, Animations, HandleMap, NlaTrack, SceneKey}, collision::move_and_slide, guards::GuardKind, interpolation::{interpolate, Interpolated}, paradox::KilledBy, noise::{Noise, KILL_RADIUS}, recording::{GhostRecording, LoopRecording}, spatial::{CharacterIndex, WallIndex}, spawn::stage::Furnace, world::{Carrying, LoopStarted}};
+
+/// Gameplay ticks per second.
+pub const TICK_RATE: u32 = 60;
+/// How close the player or a ghost needs to get to a guard to kill it.
+pub const KILL_RANGE: f32 = 1.0;
+/// How much faster than walking the player sprints.
+pub const SPRINT_FACTOR: f32 = 1.6;
 
-use super::{assets::{Action, Animations, HandleMap, NlaTrack, SceneKey}, spawn::stage::Furnace};
 
 
 
@@ -32,40 +41,37 @@ pub(super) fn plugin(app: &mut App) {
     );
 
     // Apply movement based on controls.
+    app.insert_resource(Time::<Fixed>::from_hz(f64::from(TICK_RATE)));
     app.register_type::<Movement>();
     app.add_systems(
-        Update,
-        (apply_movement)
-            .chain()
-            .in_set(AppSet::Update),
+        FixedUpdate,
+        apply_movement
+            .in_set(FixedSet::Move)
+            .run_if(in_state(InGame)),
     );
+    app.add_systems(Update, follow_player.after(interpolate).in_set(AppSet::Update));
 
     app.register_type::<Path>();
     app.register_type::<Timeloop>();
     app.register_type::<IsDead>();
-    app.register_type::<IsShooting>();
     app.register_type::<IsGoingToHell>();
     app.register_type::<Ghost>();
-    app.register_type::<GhostPath>();
 
 
 
+    app.add_systems(FixedUpdate, (
+        loop_time.in_set(FixedSet::Tick),
+        move_ghosts.in_set(FixedSet::Move),
+        (kill_npcs, go_to_hell, furnaceloop, win).in_set(FixedSet::React),
+    ).run_if(in_state(InGame)));
     app.add_systems(Update, (
-        loop_time.run_if(in_state(Screen::Playing)),
-        move_npcs.run_if(in_state(Screen::Playing)),
-        kill_npcs.run_if(in_state(Screen::Playing)).after(move_npcs),
-        detect_player.run_if(in_state(Screen::Playing)).after(move_npcs),
-        go_to_hell.run_if(in_state(Screen::Playing)),
-        animate.run_if(in_state(Screen::Playing)),
-        move_ghosts.run_if(in_state(Screen::Playing)),
-        furnaceloop.run_if(in_state(Screen::Playing)),
-        light_furnaces.run_if(in_state(Screen::Playing)),
-        win.run_if(in_state(Screen::Playing)),
-
-
+        animate.run_if(in_state(InGame)),
+        light_furnaces.run_if(in_state(InGame)),
     ));
 }
 
+/// Which way a character wants to go. Longer than 1 means sprinting,
+/// see [`is_sprinting`](super::noise::is_sprinting).
 #[derive(Component, Reflect, Default)]
 #[reflect(Component)]
 pub struct MovementController(pub Vec3);
@@ -91,7 +97,10 @@ fn record_movement_controller(
 
     // Normalize so that diagonal movement has the same speed as
     // horizontal and vertical movement.
-    let intent = intent.normalize_or_zero();
+    let mut intent = intent.normalize_or_zero();
+    if input.pressed(KeyCode::ShiftLeft) || input.pressed(KeyCode::ShiftRight) {
+        intent *= SPRINT_FACTOR;
+    }
 
     // Apply movement intent to controllers.
     for mut controller in &mut controller_query {
@@ -99,7 +108,7 @@ fn record_movement_controller(
     }
 }
 
-#[derive(Component, Reflect)]
+#[derive(Component, Clone, Reflect)]
 #[reflect(Component)]
 pub struct Movement {
     /// Since Bevy's default 2D camera setup is scaled such that
@@ -108,8 +117,13 @@ pub struct Movement {
     /// Note that physics engines may use different unit/pixel ratios.
     pub speed: f32,
     pub rotation: f32,
+    /// Size of the character's body, for colliding with walls.
+    pub radius: f32,
 }
 
+/// Where the camera sits relative to the player.
+const CAMERA_OFFSET: Vec3 = Vec3::new(-5.7, 20.7, -20.0);
+
 
 //fn gravity(
 //    time:Res<Time>,
@@ -121,13 +135,10 @@ pub struct Movement {
 //        }
 //    }
 //}
-fn apply_movement(
+pub(super) fn apply_movement(
     time: Res<Time>,
-    timeloop:Res<Timeloop>,
     mut movement_query: Query<(&MovementController, &Movement, &mut Transform, &mut Action),(Without<Wall>,Without<IsDead>)>,
-    wall_query: Query<&Transform,With<Wall>>,
-    mut camera:Query<&mut Transform,(With<Camera3d>,Without<Wall>,Without<Movement>)>,
-    mut ghostpath: ResMut<GhostPath>,
+    walls: Res<WallIndex>,
 ) {
     for (controller, movement, mut transform, mut action) in movement_query.iter_mut() {
 //        let torque = movement.rotation * controller.0.x;
@@ -135,31 +146,35 @@ fn apply_movement(
 //        let velocity = movement.speed * controller.0.z;
 //        let forward = transform.forward();
 //        let new_translation = transform.translation + forward * velocity * time.delta_seconds();
-        let new_translation = transform.translation+controller.0*movement.speed*time.delta_seconds();
-
-        for walltransform in wall_query.iter(){
-            if new_translation.x>walltransform.translation.x-walltransform.scale.x &&
-                new_translation.x<walltransform.translation.x+walltransform.scale.x &&
-                new_translation.z>walltransform.translation.z-walltransform.scale.z &&
-                new_translation.z<walltransform.translation.z+walltransform.scale.z{
-                    return;
-                }
-        }
-        transform.translation = new_translation;
-        action.new_track=NlaTrack::Idle;
-        if controller.0.length()>0.5{
-            action.new_track = NlaTrack::Walk;
-            let new_rotation = transform.looking_to(controller.0, Vec3::Y);
-            if new_rotation.rotation.angle_between(transform.rotation) >PI/10.0{
-                ghostpath.points.push((timeloop.current_time+f32::from(timeloop.gen)*timeloop.max_time,transform.translation));
-            }
-            *transform = new_rotation;
-        }
-        let mut cam =camera.single_mut();
-        cam.translation=transform.translation+Vec3::new(-5.7, 20.7,-20.0);
+        action.new_track = step_character(&mut transform, controller.0, movement, &walls, time.delta_seconds());
     }
 }
 
+/// Move a character for one tick of `input`, and pick the animation that goes with it.
+/// Ghosts replay the player through this too, so it must only depend on its arguments.
+fn step_character(transform:&mut Transform, input:Vec3, movement:&Movement, walls:&WallIndex, delta:f32)->NlaTrack{
+    let motion = input*movement.speed*delta;
+    let from = transform.translation.xz();
+    let obstacles = walls.near(from, motion.xz().length() + 2.0*movement.radius);
+    let position = move_and_slide(from, motion.xz(), movement.radius, &obstacles);
+    transform.translation = Vec3::new(position.x,transform.translation.y,position.y);
+    if input.length()>0.5{
+        *transform = transform.looking_to(input, Vec3::Y);
+        return NlaTrack::Walk;
+    }
+    NlaTrack::Idle
+}
+
+pub fn follow_player(
+    player:Query<&Transform,(With<Player>,Without<Camera3d>)>,
+    mut camera:Query<&mut Transform,(With<Camera3d>,Without<Player>)>,
+){
+    let (Ok(player),Ok(mut camera)) = (player.get_single(),camera.get_single_mut()) else{
+        return;
+    };
+    camera.translation = player.translation+CAMERA_OFFSET;
+}
+
 
 #[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Default, Reflect)]
 #[reflect(Component)]
@@ -168,6 +183,8 @@ pub struct Npc;
 #[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Default, Reflect)]
 #[reflect(Component)]
 pub struct IsDead;
+/// A past loop of the player, doing everything the player did back then:
+/// killing guards, keeping furnaces lit and getting seen.
 #[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Default, Reflect)]
 #[reflect(Component)]
 pub struct Ghost{
@@ -180,9 +197,13 @@ pub struct IsGoingToHell{
     pub countdown: f32,
 }
 
-#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Default, Reflect)]
-#[reflect(Component)]
-pub struct IsShooting;
+/// The run is over. Leaving the stage for it is up to whoever is watching,
+/// since a replay keeps showing the stage after it ends.
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunOver{
+    Lost,
+    Won,
+}
 
 #[derive(Component, Debug, Clone, Default, Reflect)]
 #[reflect(Component)]
@@ -190,123 +211,173 @@ pub struct Path{
     pub points:Vec<(f32,Vec3)>,
 }
 
-#[derive(Resource, Reflect)]
-#[reflect(Resource)]
-pub struct GhostPath{
-    pub points:Vec<(f32,Vec3)>,
-}
 
-
-#[derive(Resource, Reflect)]
+/// Where we are in the time loop, counted in gameplay ticks.
+#[derive(Resource, Debug, Clone, Reflect)]
 #[reflect(Resource)]
 pub struct Timeloop {
-    pub current_time : f32,
-    pub max_time : f32,
+    /// Ticks since the current loop started.
+    pub tick : u32,
+    /// Length of one loop.
+    pub max_ticks : u32,
+    /// How many loops have been completed.
     pub gen:u16,
 }
 
+impl Timeloop {
+    pub fn new(max_time:f32)->Self{
+        Self{
+            tick:0,
+            max_ticks:seconds_to_ticks(max_time).max(1),
+            gen:0,
+        }
+    }
 
+    /// Seconds since the current loop started.
+    pub fn current_time(&self)->f32{
+        ticks_to_seconds(self.tick)
+    }
 
+    pub fn max_time(&self)->f32{
+        ticks_to_seconds(self.max_ticks)
+    }
+}
+
+pub fn seconds_to_ticks(seconds:f32)->u32{
+    (seconds*TICK_RATE as f32).round() as u32
+}
+
+pub fn ticks_to_seconds(ticks:u32)->f32{
+    ticks as f32/TICK_RATE as f32
+}
+
+/// Advance the loop by one tick. Runs after everything else,
+/// so the rest of the tick sees the same [`Timeloop::tick`] throughout.
 pub fn loop_time(
     mut timeloop: ResMut<Timeloop>,
-    time: Res<Time<Virtual>>,
     mut commands: Commands,
     scene_handles: Res<HandleMap<SceneKey>>,
+    mut recording: ResMut<GhostRecording>,
+    player: Query<(&Transform,&Movement),With<Player>>,
+    mut ghosts: Query<(&Ghost,&mut Transform,&mut Interpolated),(Without<Player>,Without<IsDead>)>,
 ){
-    timeloop.current_time += time.delta_seconds();
-    if timeloop.current_time>timeloop.max_time{
-        timeloop.current_time %= timeloop.max_time;
-
-        commands.spawn((
-            Name::new("Ghost"),
-            SceneBundle{
-                scene:scene_handles[&SceneKey::Character].clone_weak(),
-                ..Default::default()
-            },
-            Action{
-                current_track:NlaTrack::Idle,
-                new_track:NlaTrack::Walk,
-            },
-            Npc,
-            Ghost{gen: timeloop.gen},
-            StateScoped(Screen::Playing),
-        ));
+    timeloop.tick += 1;
+    if timeloop.tick>=timeloop.max_ticks{
+        timeloop.tick = 0;
+
+        // Older ghosts relive their loop from the start, the dead ones stay where they fell.
+        for (ghost,mut transform,mut interpolated) in ghosts.iter_mut(){
+            if let Some(recorded) = recording.loops.get(usize::from(ghost.gen)){
+                *transform = recorded.start;
+                *interpolated = Interpolated::new(recorded.start);
+            }
+        }
+
+        // The new ghost replays the loop that just ended, from its start.
+        let Ok((player,movement)) = player.get_single() else{
+            return;
+        };
+        let (transform, carrying) = recording.loops.get(usize::from(timeloop.gen)).map_or((*player, vec![]), |finished| (finished.start, finished.carrying.clone()));
+        commands.spawn(ghost_bundle(&scene_handles, transform, movement.clone(), timeloop.gen, carrying));
+        recording.loops.push(LoopRecording::new(*player));
         timeloop.gen += 1;
+        commands.trigger(LoopStarted{gen: timeloop.gen});
 
     }
     
 }
 
+/// Everything a ghost of loop `gen` needs, standing at `transform`.
+pub fn ghost_bundle(
+    scene_handles:&HandleMap<SceneKey>,
+    transform:Transform,
+    movement:Movement,
+    gen:u16,
+    carrying:Vec<String>,
+)->impl Bundle{
+    (
+        Name::new("Ghost"),
+        SceneBundle{
+            scene:scene_handles[&SceneKey::Character].clone_weak(),
+            transform,
+            ..Default::default()
+        },
+        Interpolated::new(transform),
+        Action{
+            current_track:NlaTrack::Idle,
+            new_track:NlaTrack::Idle,
+        },
+        movement,
+        Ghost{gen},
+        Carrying(carrying),
+        StateScoped(InGame),
+    )
+}
+
+/// Replay what the player did at this tick of each ghost's loop.
 pub fn move_ghosts(
+    time: Res<Time>,
     timeloop:Res<Timeloop>,
-    mut ghosts:Query<(&mut Transform,&Ghost),Without<IsDead>>,
-    path: Res<GhostPath>,
+    mut ghosts:Query<(&mut Transform,&mut Action,&Movement,&Ghost),Without<IsDead>>,
+    walls: Res<WallIndex>,
+    recording: Res<GhostRecording>,
 ){
-    for (mut transform,ghost) in ghosts.iter_mut(){
-        let mut nex_point = &(0.0,Vec3::ZERO);
-        let mut prev_point = &(0.0,Vec3::ZERO);
-        for (i,i_point) in path.points.iter().enumerate(){
-            if timeloop.current_time + f32::from(ghost.gen) *timeloop.max_time < i_point.0 {
-                nex_point = i_point;
-                prev_point = match i==0{
-                    true=>&(0.0,Vec3::ZERO),
-                    false=>path.points.get(i-1).expect("i fucking empty broooo"),
-                };
-                break;
-            }
-        }
-        let mut diff = nex_point.0-prev_point.0;
-        if diff<0.0{diff= (timeloop.current_time + f32::from(ghost.gen) *timeloop.max_time)+nex_point.0-prev_point.0;}
-        let point_diff = nex_point.1-prev_point.1;
-        let mut time_since_prev = (timeloop.current_time + f32::from(ghost.gen) *timeloop.max_time)-prev_point.0;
-        if time_since_prev < 0.0{time_since_prev +=timeloop.max_time};
-        transform.translation = prev_point.1 + point_diff*time_since_prev/diff;
-        *transform = transform.looking_to(point_diff, Vec3::Y);
+    for (mut transform,mut action,movement,ghost) in ghosts.iter_mut(){
+        let sample = recording.sample(ghost.gen, timeloop.tick).unwrap_or_default();
+        step_character(&mut transform, sample.input, movement, &walls, time.delta_seconds());
+        action.new_track = sample.track;
     }
 }
 
-pub fn move_npcs(
-    timeloop:Res<Timeloop>,
-    mut npcs:Query<(&Path,&mut Transform),(Without<IsDead>,Without<Ghost>)>,
-){
-    for (path,mut transform) in npcs.iter_mut(){
-   //     let mut previous_point = path.points.last().expect("path fucking empty bruv");
- //       let mut next_point = previous_point;
-        let mut nex_point = path.points.first().expect("first empty");
-        let mut prev_point = path.points.last().expect("last empty");
-        for (i,i_point) in path.points.iter().enumerate(){
-            if timeloop.current_time < i_point.0 {
-                nex_point = i_point;
-                prev_point = match i==0{
-                    true=>path.points.last().expect("last fuckin empty bruv"),
-                    false=>path.points.get(i-1).expect("i fucking empty broooo"),
-                };
-                break;
-            }
+/// Position and walking direction at `time` along looping waypoints,
+/// the way guards follow their [`Path`].
+pub fn sample_path(points:&[(f32,Vec3)], time:f32, max_time:f32)->Option<(Vec3,Vec3)>{
+    let mut nex_point = points.first()?;
+    let mut prev_point = points.last()?;
+    for (i,i_point) in points.iter().enumerate(){
+        if time < i_point.0 {
+            nex_point = i_point;
+            prev_point = match i==0{
+                true=>points.last()?,
+                false=>&points[i-1],
+            };
+            break;
         }
-        let mut diff = nex_point.0-prev_point.0;
-        if diff<0.0{diff= timeloop.max_time+nex_point.0-prev_point.0;}
-        let point_diff = nex_point.1-prev_point.1;
-        let mut time_since_prev = timeloop.current_time-prev_point.0;
-        if time_since_prev < 0.0{time_since_prev +=timeloop.max_time};
-        transform.translation = prev_point.1 + point_diff*time_since_prev/diff;
-        *transform = transform.looking_to(point_diff, Vec3::Y);
-        
-
     }
+    let mut diff = nex_point.0-prev_point.0;
+    if diff<=0.0{diff= max_time+nex_point.0-prev_point.0;}
+    let point_diff = nex_point.1-prev_point.1;
+    let mut time_since_prev = time-prev_point.0;
+    if time_since_prev < 0.0{time_since_prev +=max_time};
+    if diff<=0.0{return Some((prev_point.1,point_diff));}
+    Some((prev_point.1 + point_diff*time_since_prev/diff,point_diff))
 }
 
+/// The player and their ghosts kill any guard they get close to, if its
+/// [`KillRule`](super::guards::KillRule) lets them.
 pub fn kill_npcs(
-    mut npcs:Query<(&Npc,&Transform,Entity,&mut Action)>,
-    player: Query<(&Player,&Transform)>,
+    timeloop:Res<Timeloop>,
+    mut npcs:Query<(&Transform,Entity,&GuardKind,&mut Action),(With<Npc>,Without<IsDead>)>,
+    killers: Query<(&Transform,Option<&Ghost>),(Or<(With<Player>,With<Ghost>)>,Without<IsDead>)>,
+    characters: Res<CharacterIndex>,
     mut commands:Commands,
 ){
-    for (_,playertransform) in player.iter(){
-        for (_, enemytransform,entity,mut action) in npcs.iter_mut(){
-            let diff = enemytransform.translation-playertransform.translation;
-            if diff.length()<1.0{
-                commands.entity(entity).insert(IsDead);
+    for (killertransform,ghost) in killers.iter(){
+        for (nearby,_) in characters.near(killertransform.translation, KILL_RANGE){
+            let Ok((enemytransform,entity,kind,mut action)) = npcs.get_mut(nearby) else{
+                continue;
+            };
+            let diff = enemytransform.translation-killertransform.translation;
+            if diff.length()<KILL_RANGE && kind.profile().kill_rule.allows(enemytransform, killertransform.translation){
+                commands.entity(entity).insert(IsDead).insert(KilledBy{
+                    gen:ghost.map_or(timeloop.gen, |ghost| ghost.gen),
+                    tick:timeloop.tick,
+                });
                 action.new_track = NlaTrack::Die;
+                commands.trigger(Noise{
+                    position:enemytransform.translation,
+                    radius:KILL_RADIUS,
+                });
             }
         }
     }
@@ -319,59 +390,10 @@ pub fn kill_npcs(
 //    }
 //}
 
-fn line_collision(a:Vec3,b:Vec3,c:Vec3,d:Vec3)->bool{
-    let u_a = ((d.x-c.x)*(a.z-c.z) - (d.z-c.z)*(a.x-c.x)) / ((d.z-c.z)*(b.x-a.x) - (d.x-c.x)*(b.z-a.z));
-    let u_b = ((b.x-a.x)*(a.z-c.z) - (b.z-a.z)*(a.x-c.x)) / ((d.z-c.z)*(b.x-a.x) - (d.x-c.x)*(b.z-a.z));
-    if (0.0..=1.0).contains(&u_a)&&(0.0..=1.0).contains(&u_b){return true;}
-    false
-}
-
-pub fn detect_player(
-    players: Query<(&Transform,Entity,&Player),(Without<IsGoingToHell>,Without<Npc>,Without<Wall>,Without<IsDead>)>,
-    mut enemies: Query<(&mut Transform,Entity,&mut Action, &Npc),(Without<IsDead>,Without<Player>,Without<Wall>,Without<IsShooting>)>,
-    walls: Query<&Transform,(With<Wall>,Without <Player>,Without<Npc>)>,
-    mut commands:Commands,
-){
-    for (player,player_id,_) in players.iter(){ 
-        for (mut enemy,enemy_id,mut action,_) in enemies.iter_mut(){
-
-            let diff = player.translation-enemy.translation;
-            let angle = diff.angle_between(*enemy.forward());
-            if angle < PI/4.0{
-                let mut is_blocked = false;
-                for wall in walls.iter(){
-                    let wall0 = Vec3::new(wall.translation.x+wall.scale.x,0.0,wall.translation.z+wall.scale.z);
-                    let wall1 = Vec3::new(wall.translation.x-wall.scale.x,0.0,wall.translation.z+wall.scale.z);
-                    let wall2 = Vec3::new(wall.translation.x-wall.scale.x,0.0,wall.translation.z-wall.scale.z);
-                    let wall3 = Vec3::new(wall.translation.x+wall.scale.x,0.0,wall.translation.z-wall.scale.z);
-
-                    if  line_collision(enemy.translation, player.translation, wall0, wall1)||
-                        line_collision(enemy.translation, player.translation, wall1, wall2)||
-                        line_collision(enemy.translation, player.translation, wall2, wall3)||
-                        line_collision(enemy.translation, player.translation, wall3, wall0)
-                        {
-                            is_blocked = true;
-                            continue;
-                        }
-                    
-
-                }
-                if !is_blocked{
-                    commands.entity(enemy_id).insert(IsShooting);
-                    *enemy = enemy.looking_at(player.translation, Vec3::Y);
-                    commands.entity(player_id).insert(IsDead).
-                        insert(IsGoingToHell{countdown:0.7});
-                    action.new_track = NlaTrack::Shoot;
-                }
-            }
-        }
-    }
-}
-
 pub fn go_to_hell(
-    mut next_screen: ResMut<NextState<Screen>>,
+    mut commands: Commands,
     mut deadman:Query<(&mut IsGoingToHell,&mut Action)>,
-    time: Res<Time<Virtual>>,
+    time: Res<Time>,
 ){
     for (mut hell,mut action) in deadman.iter_mut(){
         action.new_track = NlaTrack::Die;
@@ -379,7 +401,7 @@ pub fn go_to_hell(
             hell.countdown -= time.delta_seconds();
         }
         else{
-            next_screen.set(Screen::Hell);
+            commands.trigger(RunOver::Lost);
         }
     }
 }
@@ -388,26 +410,24 @@ fn light_furnaces(
     mut lights: Query<(&mut PointLight,&Furnace)>,
 ){
     for (mut light,coal) in lights.iter_mut(){
-        light.intensity= f32::powf(1.8, coal.countdown);
+        light.intensity= coal.intensity();
     }
 }
 
-fn furnaceloop(
+pub fn furnaceloop(
     mut furnaces : Query<(&mut Furnace,&Transform)>,
-    characters: Query<(&Transform,AnyOf<(&Player,&Npc,&Ghost)>)>,
-    time: Res<Time<Virtual>>,
-    mut next_screen: ResMut<NextState<Screen>>,
+    characters: Res<CharacterIndex>,
+    time: Res<Time>,
+    mut commands: Commands,
 
 ){
     for (mut temperature, furnace) in furnaces.iter_mut(){
-        for (character,_) in characters.iter(){
-            if character.translation.distance(furnace.translation) < 3.0{
-                temperature.countdown = 0.0;
-            }
+        if characters.near(furnace.translation, 3.0).any(|(_,character)| character.distance(furnace.translation) < 3.0){
+            temperature.countdown = 0.0;
         }
         temperature.countdown += time.delta_seconds();
         if temperature.countdown > 45.0{
-            next_screen.set(Screen::Hell);
+            commands.trigger(RunOver::Lost);
         }
     }
 }
@@ -455,16 +475,12 @@ fn animate(
 
 }
 
+/// The run is won once every guard that can die is dead, cameras keep watching regardless.
 fn win(
-    badguys: Query<&Npc,(Without<IsDead>,Without<Ghost>)>,
-    mut next_screen: ResMut<NextState<Screen>>,
-
-){
-   let mut i = 0;
-    for _ in badguys.iter(){
-        i+=1;
-    }
-    if i == 0{
-        next_screen.set(Screen::Win);
+    guards: Query<&GuardKind, (With<Npc>, Without<IsDead>, Without<Ghost>)>,
+    mut commands: Commands,
+) {
+    if !guards.iter().any(|kind| kind.can_be_killed()) {
+        commands.trigger(RunOver::Won);
     }
 }
\ No newline at end of file
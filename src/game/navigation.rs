@@ -0,0 +1,554 @@
+//! Finding a way around walls.
+//! The floor is split into a grid of small cells, and every cell a
+//! character fits in without touching a wall is walkable. Routes are found
+//! with A* over that grid and then straightened, so they only turn at
+//! corners. Guards use it to walk their rounds through waypoints that only
+//! say where to be, and to get to whatever they are investigating.
+//! Guards can't open doors, so closed doors block cells on top of the walls,
+//! and [`sync_doors`](super::world::sync_doors) unblocks them as doors open.
+//! This needs nothing but the level data, so validation can use it headless.
+
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, VecDeque},
+};
+
+use bevy::prelude::*;
+
+use super::{
+    collision::{Obstacle, CHARACTER_RADIUS},
+    level::Level,
+};
+
+pub(super) fn plugin(app: &mut App) {
+    app.init_resource::<NavGrid>();
+}
+
+/// Side of a grid cell.
+pub const CELL: f32 = 0.5;
+/// Room left around everything in a level, so characters can walk around the outside.
+const MARGIN: f32 = 3.0;
+/// How far from a blocked cell to look for a walkable one to start or end a route at.
+const SNAP_CELLS: i32 = 4;
+/// Costs of a straight and a diagonal step, in tenths of a cell.
+const STRAIGHT_COST: u32 = 10;
+const DIAGONAL_COST: u32 = 14;
+
+/// Which parts of the floor a character can stand on.
+#[derive(Resource, Debug, Clone, Default)]
+pub struct NavGrid {
+    /// Corner of the first cell.
+    min: Vec2,
+    /// Number of cells along x and z.
+    size: IVec2,
+    /// Cells clear of walls, whatever the doors are doing.
+    clear: Vec<bool>,
+    walkable: Vec<bool>,
+    /// Counts changes to `walkable`, so [`DistanceField`]s know when they are out of date.
+    version: u32,
+}
+
+/// How far every cell is from one goal, walking. Going downhill from anywhere
+/// follows a shortest route there, so a guard that keeps heading for the same
+/// place only searches the grid once, and still only goes by where it stands.
+#[derive(Debug, Clone, Default)]
+pub struct DistanceField {
+    /// The cell that was asked for, which may not be walkable itself.
+    target: IVec2,
+    goal: Option<IVec2>,
+    version: u32,
+    cost: Vec<u32>,
+}
+
+impl NavGrid {
+    /// A grid covering `min` to `max`, blocked wherever a character would overlap an obstacle.
+    pub fn new(obstacles: &[Obstacle], min: Vec2, max: Vec2) -> Self {
+        let size = ((max - min) / CELL).ceil().as_ivec2().max(IVec2::ONE);
+        let mut grid = Self {
+            min,
+            size,
+            clear: vec![false; (size.x * size.y) as usize],
+            walkable: vec![],
+            version: 0,
+        };
+        for cell in grid.cells() {
+            let index = grid.index(cell);
+            grid.clear[index] = !grid.overlaps(cell, obstacles);
+        }
+        grid.walkable.clone_from(&grid.clear);
+        grid
+    }
+
+    /// Block the cells `closed` doors cover, and unblock those of every other door.
+    pub fn set_doors(&mut self, closed: &[Obstacle]) {
+        for cell in self.cells() {
+            let index = self.index(cell);
+            self.walkable[index] = self.clear[index] && !self.overlaps(cell, closed);
+        }
+        self.version = self.version.wrapping_add(1);
+    }
+
+    fn overlaps(&self, cell: IVec2, obstacles: &[Obstacle]) -> bool {
+        let center = self.center(cell);
+        obstacles
+            .iter()
+            .any(|obstacle| obstacle.penetration(center, CHARACTER_RADIUS).is_some())
+    }
+
+    /// A grid around everything in `level`, blocked by its walls and its doors,
+    /// which all start closed.
+    pub fn for_level(level: &Level) -> Self {
+        let obstacles: Vec<Obstacle> = level.walls.iter().map(|wall| wall.obstacle()).collect();
+        let points = obstacles
+            .iter()
+            .flat_map(|obstacle| {
+                let (min, max) = obstacle.bounds();
+                [min, max]
+            })
+            .chain(level.furnaces.iter().map(|furnace| furnace.translation.xz()))
+            .chain(level.switches.iter().map(|switch| switch.translation.xz()))
+            .chain(level.items.iter().map(|item| item.translation.xz()))
+            .chain(
+                level
+                    .guards
+                    .iter()
+                    .flat_map(|guard| guard.path.iter().map(|(_, point)| point.xz())),
+            )
+            .chain([level.player_start.xz()]);
+        let (min, max) = points.fold((Vec2::MAX, Vec2::MIN), |(min, max), point| {
+            (min.min(point), max.max(point))
+        });
+        let mut grid = Self::new(&obstacles, min - MARGIN, max + MARGIN);
+        let doors: Vec<Obstacle> = level.doors.iter().map(|door| door.obstacle()).collect();
+        grid.set_doors(&doors);
+        grid
+    }
+
+    pub fn cells(&self) -> impl Iterator<Item = IVec2> {
+        let size = self.size;
+        (0..size.y).flat_map(move |y| (0..size.x).map(move |x| IVec2::new(x, y)))
+    }
+
+    pub fn contains(&self, cell: IVec2) -> bool {
+        cell.cmpge(IVec2::ZERO).all() && cell.cmplt(self.size).all()
+    }
+
+    pub fn index(&self, cell: IVec2) -> usize {
+        (cell.y * self.size.x + cell.x) as usize
+    }
+
+    pub fn center(&self, cell: IVec2) -> Vec2 {
+        self.min + (cell.as_vec2() + 0.5) * CELL
+    }
+
+    /// The cell under `point`, which may be outside the grid.
+    pub fn cell(&self, point: Vec2) -> IVec2 {
+        ((point - self.min) / CELL).floor().as_ivec2()
+    }
+
+    pub fn is_walkable(&self, cell: IVec2) -> bool {
+        self.contains(cell) && self.walkable[self.index(cell)]
+    }
+
+    /// Walkable cells next to `cell`, with the cost of stepping there.
+    /// Diagonal steps can't cut corners, since a character would clip the wall.
+    fn neighbours(&self, cell: IVec2) -> impl Iterator<Item = (IVec2, u32)> + '_ {
+        const STEPS: [IVec2; 8] = [
+            IVec2::X,
+            IVec2::NEG_X,
+            IVec2::Y,
+            IVec2::NEG_Y,
+            IVec2::ONE,
+            IVec2::NEG_ONE,
+            IVec2::new(1, -1),
+            IVec2::new(-1, 1),
+        ];
+        STEPS.into_iter().filter_map(move |step| {
+            let next = cell + step;
+            if !self.is_walkable(next) {
+                return None;
+            }
+            if step.x != 0 && step.y != 0 {
+                let clear = self.is_walkable(cell + IVec2::new(step.x, 0))
+                    && self.is_walkable(cell + IVec2::new(0, step.y));
+                return clear.then_some((next, DIAGONAL_COST));
+            }
+            Some((next, STRAIGHT_COST))
+        })
+    }
+
+    /// Every cell that can be walked to from `from`, by index.
+    pub fn reachable_from(&self, from: Vec2) -> Vec<bool> {
+        let mut reached = vec![false; self.walkable.len()];
+        let Some(start) = self.nearest_walkable(self.cell(from)) else {
+            return reached;
+        };
+        reached[self.index(start)] = true;
+        let mut queue = VecDeque::from([start]);
+        while let Some(cell) = queue.pop_front() {
+            for (next, _) in self.neighbours(cell) {
+                if !reached[self.index(next)] {
+                    reached[self.index(next)] = true;
+                    queue.push_back(next);
+                }
+            }
+        }
+        reached
+    }
+
+    /// The closest walkable cell to `cell`, in case it is blocked or off the grid.
+    fn nearest_walkable(&self, cell: IVec2) -> Option<IVec2> {
+        (0..=SNAP_CELLS).find_map(|distance| {
+            (-distance..=distance)
+                .flat_map(|y| (-distance..=distance).map(move |x| IVec2::new(x, y)))
+                .filter(|offset| offset.abs().max_element() == distance)
+                .map(|offset| cell + offset)
+                .find(|&near| self.is_walkable(near))
+        })
+    }
+
+    /// Whether a character can walk in a straight line from `a` to `b`.
+    /// The cells at either end don't count, characters can stand closer to a
+    /// wall than the middle of a walkable cell.
+    pub fn is_clear(&self, a: Vec2, b: Vec2) -> bool {
+        let ends = [self.cell(a), self.cell(b)];
+        let steps = (a.distance(b) / (CELL * 0.5)).ceil().max(1.0) as usize;
+        (0..=steps).all(|step| {
+            let cell = self.cell(a.lerp(b, step as f32 / steps as f32));
+            ends.contains(&cell) || self.is_walkable(cell)
+        })
+    }
+
+    /// The corners to walk through to get from `from` to `to`, ending with `to`.
+    /// Empty if there is no way there.
+    pub fn find_path(&self, from: Vec2, to: Vec2) -> Vec<Vec2> {
+        if self.is_clear(from, to) {
+            return vec![to];
+        }
+        let (Some(start), Some(goal)) = (
+            self.nearest_walkable(self.cell(from)),
+            self.nearest_walkable(self.cell(to)),
+        ) else {
+            return vec![];
+        };
+        let estimate = |cell: IVec2| {
+            let offset = (goal - cell).abs();
+            let (short, long) = (offset.min_element() as u32, offset.max_element() as u32);
+            DIAGONAL_COST * short + STRAIGHT_COST * (long - short)
+        };
+
+        let mut cost = vec![u32::MAX; self.walkable.len()];
+        let mut came_from: Vec<Option<IVec2>> = vec![None; self.walkable.len()];
+        let mut open = BinaryHeap::new();
+        cost[self.index(start)] = 0;
+        open.push(Reverse((estimate(start), start.x, start.y)));
+        while let Some(Reverse((_, x, y))) = open.pop() {
+            let cell = IVec2::new(x, y);
+            if cell == goal {
+                break;
+            }
+            let current = cost[self.index(cell)];
+            for (next, step) in self.neighbours(cell) {
+                let next_cost = current + step;
+                if next_cost < cost[self.index(next)] {
+                    cost[self.index(next)] = next_cost;
+                    came_from[self.index(next)] = Some(cell);
+                    open.push(Reverse((next_cost + estimate(next), next.x, next.y)));
+                }
+            }
+        }
+        if cost[self.index(goal)] == u32::MAX {
+            return vec![];
+        }
+
+        let mut cells = vec![goal];
+        let mut cell = goal;
+        while let Some(previous) = came_from[self.index(cell)] {
+            cells.push(previous);
+            cell = previous;
+        }
+        cells.reverse();
+        self.straighten(from, &cells, to)
+    }
+
+    /// Every cell's walking distance to the cell under `to`, for [`follow`](Self::follow).
+    pub fn distance_field(&self, to: Vec2) -> DistanceField {
+        let target = self.cell(to);
+        let goal = self.nearest_walkable(target);
+        let mut cost = vec![u32::MAX; self.walkable.len()];
+        if let Some(goal) = goal {
+            cost[self.index(goal)] = 0;
+            let mut open = BinaryHeap::from([Reverse((0, goal.x, goal.y))]);
+            while let Some(Reverse((current, x, y))) = open.pop() {
+                let cell = IVec2::new(x, y);
+                if current > cost[self.index(cell)] {
+                    continue;
+                }
+                for (next, step) in self.neighbours(cell) {
+                    let next_cost = current + step;
+                    if next_cost < cost[self.index(next)] {
+                        cost[self.index(next)] = next_cost;
+                        open.push(Reverse((next_cost, next.x, next.y)));
+                    }
+                }
+            }
+        }
+        DistanceField {
+            target,
+            goal,
+            version: self.version,
+            cost,
+        }
+    }
+
+    /// Whether `field` still leads to `to`, with the doors as they are now.
+    pub fn is_current(&self, field: &DistanceField, to: Vec2) -> bool {
+        field.version == self.version
+            && field.target == self.cell(to)
+            && field.cost.len() == self.walkable.len()
+    }
+
+    /// Like [`find_path`](Self::find_path), going downhill on a `field` made for `to`.
+    pub fn follow(&self, field: &DistanceField, from: Vec2, to: Vec2) -> Vec<Vec2> {
+        if self.is_clear(from, to) {
+            return vec![to];
+        }
+        let (Some(mut cell), Some(goal)) = (self.nearest_walkable(self.cell(from)), field.goal)
+        else {
+            return vec![];
+        };
+        if field.cost[self.index(cell)] == u32::MAX {
+            return vec![];
+        }
+        let mut cells = vec![cell];
+        while cell != goal {
+            let here = field.cost[self.index(cell)];
+            // Some neighbour is always exactly one step closer, the one the cost came through.
+            let Some((next, _)) = self
+                .neighbours(cell)
+                .find(|&(next, step)| field.cost[self.index(next)].saturating_add(step) == here)
+            else {
+                return vec![];
+            };
+            cells.push(next);
+            cell = next;
+        }
+        self.straighten(from, &cells, to)
+    }
+
+    /// Pull a route through `cells` straight, keeping only the points it has to turn at.
+    fn straighten(&self, from: Vec2, cells: &[IVec2], to: Vec2) -> Vec<Vec2> {
+        let points: Vec<Vec2> = cells
+            .iter()
+            .map(|&cell| self.center(cell))
+            .chain([to])
+            .collect();
+        let mut corners = vec![];
+        let mut position = from;
+        let mut next = 0;
+        while next < points.len() {
+            let furthest = (next..points.len())
+                .rev()
+                .find(|&index| self.is_clear(position, points[index]))
+                .unwrap_or(next);
+            position = points[furthest];
+            corners.push(position);
+            next = furthest + 1;
+        }
+        corners
+    }
+
+    /// Turn waypoints that only say where to be and when into ones that go
+    /// around walls. Corners in between are timed so the walk keeps an even pace.
+    /// Waypoints that can't be reached from each other are joined by a straight line.
+    /// The result is sorted by time, as [`sample_path`](super::movement::sample_path) expects.
+    pub fn route_path(&self, points: &[(f32, Vec3)], max_time: f32) -> Vec<(f32, Vec3)> {
+        if points.len() < 2 {
+            return points.to_vec();
+        }
+        let mut routed = vec![];
+        for (index, &(time, point)) in points.iter().enumerate() {
+            routed.push((time, point));
+            let (next_time, next_point) = points[(index + 1) % points.len()];
+            let mut duration = next_time - time;
+            if duration <= 0.0 {
+                duration += max_time;
+            }
+            let corners = self.find_path(point.xz(), next_point.xz());
+            if corners.len() < 2 {
+                continue;
+            }
+            let mut previous = point.xz();
+            let length: f32 = corners
+                .iter()
+                .map(|&corner| {
+                    let step = previous.distance(corner);
+                    previous = corner;
+                    step
+                })
+                .sum();
+            let mut walked = 0.0;
+            previous = point.xz();
+            // The last corner is the next waypoint itself.
+            for &corner in &corners[..corners.len() - 1] {
+                walked += previous.distance(corner);
+                previous = corner;
+                let at = time + duration * walked / length.max(f32::EPSILON);
+                let height = point.y.lerp(next_point.y, walked / length.max(f32::EPSILON));
+                routed.push((at.rem_euclid(max_time), Vec3::new(corner.x, height, corner.y)));
+            }
+        }
+        routed.sort_by(|a, b| a.0.total_cmp(&b.0));
+        routed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::collision::Collider;
+
+    fn wall(center: Vec2, half_size: Vec2) -> Obstacle {
+        Obstacle::new(
+            &Collider::Cuboid { half_size },
+            Vec3::new(center.x, 0.0, center.y),
+            Quat::IDENTITY,
+        )
+    }
+
+    fn grid(walls: &[Obstacle]) -> NavGrid {
+        NavGrid::new(walls, Vec2::splat(-10.0), Vec2::splat(10.0))
+    }
+
+    /// Whether every leg of a route can be walked in a straight line.
+    fn is_walkable_route(grid: &NavGrid, from: Vec2, route: &[Vec2]) -> bool {
+        let mut previous = from;
+        route.iter().all(|&corner| {
+            let clear = grid.is_clear(previous, corner);
+            previous = corner;
+            clear
+        })
+    }
+
+    #[test]
+    fn blocked_start_snaps_to_the_nearest_walkable_cell() {
+        let grid = grid(&[wall(Vec2::ZERO, Vec2::new(1.0, 5.0))]);
+        let from = Vec2::new(0.5, 0.0);
+        let to = Vec2::new(6.0, 0.0);
+        assert!(!grid.is_walkable(grid.cell(from)));
+
+        let route = grid.find_path(from, to);
+        assert_eq!(route.last(), Some(&to));
+        assert!(is_walkable_route(&grid, route[0], &route[1..]));
+    }
+
+    #[test]
+    fn start_deep_inside_a_wall_has_no_route() {
+        let grid = grid(&[wall(Vec2::ZERO, Vec2::splat(5.0))]);
+        assert!(grid.find_path(Vec2::ZERO, Vec2::new(8.0, 8.0)).is_empty());
+    }
+
+    #[test]
+    fn walled_in_goal_has_no_route() {
+        let grid = grid(&[
+            wall(Vec2::new(0.0, 3.0), Vec2::new(3.5, 0.5)),
+            wall(Vec2::new(0.0, -3.0), Vec2::new(3.5, 0.5)),
+            wall(Vec2::new(3.0, 0.0), Vec2::new(0.5, 3.5)),
+            wall(Vec2::new(-3.0, 0.0), Vec2::new(0.5, 3.5)),
+        ]);
+        assert!(grid.find_path(Vec2::new(-7.0, -7.0), Vec2::ZERO).is_empty());
+        assert!(grid.find_path(Vec2::ZERO, Vec2::new(-7.0, -7.0)).is_empty());
+    }
+
+    #[test]
+    fn closed_doors_block_until_opened() {
+        let mut grid = grid(&[
+            wall(Vec2::new(0.0, 5.5), Vec2::new(0.5, 4.5)),
+            wall(Vec2::new(0.0, -5.5), Vec2::new(0.5, 4.5)),
+        ]);
+        let door = wall(Vec2::ZERO, Vec2::new(0.5, 1.0));
+        let (from, to) = (Vec2::new(-5.0, 0.0), Vec2::new(5.0, 0.0));
+        grid.set_doors(&[door]);
+        assert!(grid.find_path(from, to).is_empty());
+
+        grid.set_doors(&[]);
+        assert_eq!(grid.find_path(from, to), vec![to]);
+    }
+
+    #[test]
+    fn diagonal_steps_do_not_cut_corners() {
+        let mut grid = NavGrid {
+            min: Vec2::ZERO,
+            size: IVec2::splat(3),
+            clear: vec![true; 9],
+            walkable: vec![true; 9],
+            version: 0,
+        };
+        let blocked = grid.index(IVec2::new(1, 0));
+        grid.clear[blocked] = false;
+        grid.set_doors(&[]);
+
+        let steps: Vec<IVec2> = grid.neighbours(IVec2::ZERO).map(|(cell, _)| cell).collect();
+        assert!(!steps.contains(&IVec2::new(1, 1)));
+        assert!(steps.contains(&IVec2::new(0, 1)));
+        let steps: Vec<IVec2> = grid.neighbours(IVec2::new(0, 1)).map(|(cell, _)| cell).collect();
+        assert!(steps.contains(&IVec2::new(1, 2)));
+    }
+
+    #[test]
+    fn routes_around_a_corner_stay_walkable() {
+        let grid = grid(&[wall(Vec2::new(2.0, 2.0), Vec2::splat(2.0))]);
+        let from = Vec2::new(-1.0, 5.0);
+        let route = grid.find_path(from, Vec2::new(5.0, -1.0));
+        assert!(route.len() > 1);
+        assert!(is_walkable_route(&grid, from, &route));
+    }
+
+    #[test]
+    fn distance_fields_route_like_searching_does() {
+        let grid = grid(&[
+            wall(Vec2::new(2.0, 2.0), Vec2::splat(2.0)),
+            wall(Vec2::new(-4.0, -1.0), Vec2::new(0.5, 4.0)),
+        ]);
+        let to = Vec2::new(5.0, -1.0);
+        let field = grid.distance_field(to);
+        for from in [Vec2::new(-1.0, 5.0), Vec2::new(-7.0, 0.0), Vec2::new(-8.0, -8.0)] {
+            let searched = grid.find_path(from, to);
+            let followed = grid.follow(&field, from, to);
+            assert!(!followed.is_empty(), "{from}");
+            assert_eq!(followed.last(), searched.last(), "{from}");
+            assert!(is_walkable_route(&grid, from, &followed), "{from}: {followed:?}");
+        }
+    }
+
+    #[test]
+    fn distance_fields_go_stale_with_the_target_or_doors() {
+        let mut grid = grid(&[
+            wall(Vec2::new(0.0, 5.5), Vec2::new(0.5, 4.5)),
+            wall(Vec2::new(0.0, -5.5), Vec2::new(0.5, 4.5)),
+        ]);
+        let (from, to) = (Vec2::new(-5.0, 0.0), Vec2::new(5.0, 3.0));
+        let field = grid.distance_field(to);
+        assert!(grid.is_current(&field, to + Vec2::splat(0.01)));
+        assert!(!grid.is_current(&field, to + Vec2::X * CELL));
+        assert!(!grid.is_current(&DistanceField::default(), to));
+
+        grid.set_doors(&[wall(Vec2::ZERO, Vec2::new(0.5, 1.0))]);
+        assert!(!grid.is_current(&field, to));
+        assert!(grid.follow(&grid.distance_field(to), from, to).is_empty());
+    }
+
+    #[test]
+    fn wrapped_corner_times_stay_sorted() {
+        let grid = grid(&[wall(Vec2::ZERO, Vec2::new(0.5, 3.0))]);
+        let max_time = 10.0;
+        let points = [(2.0, Vec3::new(-5.0, 0.0, 0.0)), (9.0, Vec3::new(5.0, 0.0, 0.0))];
+        let routed = grid.route_path(&points, max_time);
+
+        assert!(routed.windows(2).all(|pair| pair[0].0 <= pair[1].0));
+        assert!(routed.iter().all(|&(time, _)| (0.0..max_time).contains(&time)));
+        assert!(points.iter().all(|point| routed.contains(point)));
+        // Going back from the last waypoint to the first crosses the end of the loop.
+        assert!(routed.iter().any(|&(time, _)| time < points[0].0));
+    }
+}
@@ -0,0 +1,235 @@
+//! Sounds guards can hear.
+//! Sprinting footsteps, kills and thrown pebbles all make a [`Noise`] that
+//! carries for a radius. Every wall between the noise and a guard muffles
+//! it, so it carries less far through them. Guards that hear it go and
+//! investigate, unless they are busy with someone they saw.
+//! Throws are part of the player's input, so ghosts throw again in their loop.
+
+use std::borrow::Borrow;
+
+use bevy::prelude::*;
+
+use super::{
+    assets::SfxKey,
+    audio::sfx::PlaySfx,
+    collision::Obstacle,
+    guards::GuardAi,
+    movement::{Ghost, IsDead, MovementController, Npc, Timeloop, TICK_RATE},
+    recording::GhostRecording,
+    spatial::WallIndex,
+    spawn::player::Player,
+};
+use crate::{
+    screen::{InGame, Screen},
+    AppSet, FixedSet,
+};
+
+pub(super) fn plugin(app: &mut App) {
+    app.register_type::<ThrowInput>();
+    app.init_resource::<ThrowInput>();
+    app.register_type::<NoiseRing>();
+
+    app.observe(alert_guards);
+    app.observe(spawn_noise_ring);
+    app.add_systems(
+        Update,
+        record_throw_input
+            .in_set(AppSet::RecordInput)
+            .run_if(in_state(Screen::Playing)),
+    );
+    app.add_systems(
+        FixedUpdate,
+        (sprint_footsteps, throw_pebbles)
+            .in_set(FixedSet::React)
+            .run_if(in_state(InGame)),
+    );
+    app.add_systems(Update, draw_noise_rings.run_if(in_state(InGame)));
+}
+
+/// How far sprinting footsteps carry.
+const FOOTSTEP_RADIUS: f32 = 7.0;
+/// Ticks between two footsteps while sprinting.
+const FOOTSTEP_TICKS: u32 = TICK_RATE / 3;
+/// How far a kill carries.
+pub const KILL_RADIUS: f32 = 8.0;
+/// How far a pebble landing carries.
+const PEBBLE_RADIUS: f32 = 10.0;
+/// How far a pebble flies when nothing is in the way.
+const THROW_DISTANCE: f32 = 8.0;
+/// How much of its radius a noise keeps through each wall.
+const WALL_DAMPING: f32 = 0.4;
+/// How long a noise ring stays on screen, in seconds.
+const RING_TIME: f32 = 0.6;
+
+/// Something loud happened at `position`.
+#[derive(Event, Debug, Clone, Copy, PartialEq)]
+pub struct Noise {
+    pub position: Vec3,
+    /// How far it carries with nothing in the way.
+    pub radius: f32,
+}
+
+/// Whether the player asked for a throw since the last tick.
+#[derive(Resource, Debug, Clone, Copy, Default, Reflect)]
+#[reflect(Resource)]
+pub struct ThrowInput {
+    pub pressed: bool,
+}
+
+/// Shows where a noise was made and how far it carried.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Reflect)]
+#[reflect(Component)]
+pub struct NoiseRing {
+    pub radius: f32,
+    pub age: f32,
+}
+
+fn record_throw_input(input: Res<ButtonInput<KeyCode>>, mut throw: ResMut<ThrowInput>) {
+    // Held until a tick takes it, frames can go by without one.
+    throw.pressed |= input.just_pressed(KeyCode::Space);
+}
+
+/// How far `noise` carries towards `to`, after going through walls.
+pub fn radius_towards(noise: &Noise, to: Vec3, obstacles: &[impl Borrow<Obstacle>]) -> f32 {
+    let walls = obstacles
+        .iter()
+        .map(Borrow::borrow)
+        .filter(|obstacle: &&Obstacle| obstacle.blocks(noise.position.xz(), to.xz()))
+        .count();
+    noise.radius * WALL_DAMPING.powi(walls as i32)
+}
+
+fn alert_guards(
+    trigger: Trigger<Noise>,
+    mut guards: Query<(&Transform, &mut GuardAi), (With<Npc>, Without<IsDead>)>,
+    walls: Res<WallIndex>,
+) {
+    let noise = trigger.event();
+    for (transform, mut ai) in &mut guards {
+        let distance = transform.translation.xz().distance(noise.position.xz());
+        let obstacles = walls.along(noise.position.xz(), transform.translation.xz());
+        if distance < radius_towards(noise, transform.translation, &obstacles) {
+            ai.hear(noise.position);
+        }
+    }
+}
+
+fn spawn_noise_ring(trigger: Trigger<Noise>, mut commands: Commands) {
+    let noise = trigger.event();
+    commands.spawn((
+        Name::new("Noise"),
+        SpatialBundle::from_transform(Transform::from_translation(noise.position)),
+        NoiseRing {
+            radius: noise.radius,
+            age: 0.0,
+        },
+        StateScoped(InGame),
+    ));
+}
+
+/// Sprinting is loud. The player hears their own steps, ghosts only make guards hear them.
+fn sprint_footsteps(
+    timeloop: Res<Timeloop>,
+    player: Query<(&Transform, &MovementController), (With<Player>, Without<IsDead>)>,
+    ghosts: Query<(&Transform, &Ghost), Without<IsDead>>,
+    recording: Res<GhostRecording>,
+    mut commands: Commands,
+) {
+    if timeloop.tick % FOOTSTEP_TICKS != 0 {
+        return;
+    }
+    let noise = |transform: &Transform| Noise {
+        position: transform.translation,
+        radius: FOOTSTEP_RADIUS,
+    };
+    for (transform, controller) in &player {
+        if is_sprinting(controller.0) {
+            commands.trigger(noise(transform));
+            let steps = [SfxKey::Step1, SfxKey::Step2, SfxKey::Step3, SfxKey::Step4];
+            let step = steps[(timeloop.tick / FOOTSTEP_TICKS) as usize % steps.len()];
+            commands.trigger(PlaySfx::Key(step));
+        }
+    }
+    for (transform, ghost) in &ghosts {
+        let sample = recording.sample(ghost.gen, timeloop.tick).unwrap_or_default();
+        if is_sprinting(sample.input) {
+            commands.trigger(noise(transform));
+        }
+    }
+}
+
+/// Whether movement input is faster than walking, see [`MovementController`].
+pub fn is_sprinting(input: Vec3) -> bool {
+    input.length() > 1.0 + f32::EPSILON
+}
+
+/// Throw a pebble ahead, which makes a noise where it lands.
+fn throw_pebbles(
+    timeloop: Res<Timeloop>,
+    mut throw: ResMut<ThrowInput>,
+    player: Query<&Transform, (With<Player>, Without<IsDead>)>,
+    ghosts: Query<(&Transform, &Ghost), Without<IsDead>>,
+    walls: Res<WallIndex>,
+    recording: Res<GhostRecording>,
+    mut commands: Commands,
+) {
+    let mut throwers: Vec<&Transform> = ghosts
+        .iter()
+        .filter(|(_, ghost)| {
+            recording
+                .sample(ghost.gen, timeloop.tick)
+                .is_some_and(|sample| sample.throw)
+        })
+        .map(|(transform, _)| transform)
+        .collect();
+    if std::mem::take(&mut throw.pressed) {
+        throwers.extend(player.iter());
+    }
+    for transform in throwers {
+        commands.trigger(Noise {
+            position: landing(transform, &walls),
+            radius: PEBBLE_RADIUS,
+        });
+    }
+}
+
+/// Where a pebble thrown from `transform` lands, short of any wall in the way.
+fn landing(transform: &Transform, walls: &WallIndex) -> Vec3 {
+    let from = transform.translation;
+    let obstacles = walls.near(from.xz(), THROW_DISTANCE);
+    let direction = transform.forward().with_y(0.0).normalize_or_zero();
+    let mut distance = THROW_DISTANCE;
+    while distance > 0.0 {
+        let to = from + direction * distance;
+        if !obstacles
+            .iter()
+            .any(|obstacle| obstacle.blocks(from.xz(), to.xz()))
+        {
+            return to;
+        }
+        distance -= 0.5;
+    }
+    from
+}
+
+fn draw_noise_rings(
+    time: Res<Time>,
+    mut rings: Query<(Entity, &Transform, &mut NoiseRing)>,
+    mut gizmos: Gizmos,
+    mut commands: Commands,
+) {
+    for (entity, transform, mut ring) in &mut rings {
+        ring.age += time.delta_seconds();
+        if ring.age > RING_TIME {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        }
+        let progress = ring.age / RING_TIME;
+        gizmos.circle(
+            transform.translation.with_y(0.05),
+            Dir3::Y,
+            ring.radius * progress,
+            Color::srgba(1.0, 1.0, 1.0, 1.0 - progress),
+        );
+    }
+}
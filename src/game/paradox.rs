@@ -0,0 +1,178 @@
+//! Notices when the present stops matching what a ghost did in its loop.
+//! Ghosts replay the past, so anything that stops them, or anything that
+//! undoes what they did, makes the timeline inconsistent. Each level picks a
+//! [`ParadoxRule`] for what happens then.
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    guards::detect_player,
+    movement::{kill_npcs, Ghost, IsDead, IsGoingToHell, Timeloop, KILL_RANGE},
+    spawn::player::Player,
+};
+use crate::{screen::InGame, AppSet, FixedSet};
+
+pub(super) fn plugin(app: &mut App) {
+    app.register_type::<ParadoxRule>();
+    app.init_resource::<ParadoxRule>();
+    app.register_type::<KilledBy>();
+    app.register_type::<Glitch>();
+
+    app.observe(apply_paradox);
+    app.add_systems(
+        FixedUpdate,
+        (detect_shot_ghosts, detect_missing_killers)
+            .after(kill_npcs)
+            .after(detect_player)
+            .in_set(FixedSet::React)
+            .run_if(in_state(InGame)),
+    );
+    app.add_systems(
+        Update,
+        flicker_glitches
+            .in_set(AppSet::Update)
+            .run_if(in_state(InGame)),
+    );
+}
+
+/// What happens when the timeline becomes inconsistent.
+#[derive(
+    Resource, Debug, Clone, Copy, PartialEq, Eq, Default, Reflect, Serialize, Deserialize,
+)]
+#[reflect(Resource)]
+pub enum ParadoxRule {
+    /// The ghost that can no longer exist disappears.
+    #[default]
+    Collapse,
+    /// Nothing changes, but it shows.
+    Glitch,
+    /// The run is lost.
+    Fail,
+}
+
+/// Who killed a guard, and when in their loop.
+/// Every later loop expects that ghost to be back at the body at that tick.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Reflect)]
+#[reflect(Component)]
+pub struct KilledBy {
+    pub gen: u16,
+    pub tick: u32,
+}
+
+#[derive(Event, Debug)]
+pub struct Paradox {
+    /// The ghost that caused it, or whatever shows it if that ghost is gone.
+    pub entity: Entity,
+    pub cause: ParadoxCause,
+}
+
+/// What broke the timeline.
+/// A guard only seeing a ghost isn't a paradox: the ghost still does what it
+/// did in its loop, until the guard shoots it. The player can't kill their own
+/// ghosts either, since [`kill_npcs`] only goes after guards.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParadoxCause {
+    /// A guard saw and shot a ghost, which never happened in its loop.
+    GhostShot,
+    /// A guard is dead, but the ghost that killed it didn't come back to do it.
+    KillerMissing { guard: Entity },
+}
+
+/// Makes something flicker for a while.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Reflect)]
+#[reflect(Component)]
+pub struct Glitch {
+    pub remaining: f32,
+}
+
+const GLITCH_TIME: f32 = 1.5;
+
+fn detect_shot_ghosts(
+    ghosts: Query<Entity, (With<Ghost>, Added<IsDead>)>,
+    mut commands: Commands,
+) {
+    for ghost in &ghosts {
+        commands.trigger(Paradox {
+            entity: ghost,
+            cause: ParadoxCause::GhostShot,
+        });
+    }
+}
+
+fn detect_missing_killers(
+    timeloop: Res<Timeloop>,
+    guards: Query<(Entity, &Transform, &KilledBy)>,
+    ghosts: Query<(Entity, &Transform, &Ghost, Has<IsDead>)>,
+    mut commands: Commands,
+) {
+    for (guard, guard_transform, killed_by) in &guards {
+        if killed_by.tick != timeloop.tick || killed_by.gen >= timeloop.gen {
+            continue;
+        }
+        let killer = ghosts.iter().find(|(_, _, ghost, _)| ghost.gen == killed_by.gen);
+        let arrived = killer.is_some_and(|(_, transform, _, is_dead)| {
+            !is_dead && transform.translation.distance(guard_transform.translation) < KILL_RANGE
+        });
+        if !arrived {
+            commands.entity(guard).remove::<KilledBy>();
+            commands.trigger(Paradox {
+                entity: killer.map_or(guard, |(ghost, ..)| ghost),
+                cause: ParadoxCause::KillerMissing { guard },
+            });
+        }
+    }
+}
+
+fn apply_paradox(
+    trigger: Trigger<Paradox>,
+    rule: Res<ParadoxRule>,
+    ghosts: Query<(), With<Ghost>>,
+    player: Query<Entity, (With<Player>, Without<IsGoingToHell>)>,
+    mut commands: Commands,
+) {
+    let paradox = trigger.event();
+    info!("Paradox: {:?}, applying {:?}", paradox.cause, *rule);
+    match *rule {
+        ParadoxRule::Collapse => {
+            if ghosts.contains(paradox.entity) {
+                commands.entity(paradox.entity).despawn_recursive();
+            }
+        }
+        ParadoxRule::Glitch => {
+            commands.entity(paradox.entity).insert(Glitch {
+                remaining: GLITCH_TIME,
+            });
+        }
+        ParadoxRule::Fail => {
+            for player in &player {
+                commands
+                    .entity(player)
+                    .insert(IsDead)
+                    .insert(IsGoingToHell { countdown: 0.7 });
+            }
+        }
+    }
+}
+
+fn flicker_glitches(
+    time: Res<Time>,
+    mut glitches: Query<(Entity, &mut Glitch, &mut Visibility)>,
+    mut commands: Commands,
+) {
+    for (entity, mut glitch, mut visibility) in &mut glitches {
+        glitch.remaining -= time.delta_seconds();
+        if glitch.remaining <= 0.0 {
+            *visibility = Visibility::Inherited;
+            commands.entity(entity).remove::<Glitch>();
+            continue;
+        }
+        // Off for a few frames at a time, at an uneven rhythm.
+        let phase = (glitch.remaining * 23.0).sin() + (glitch.remaining * 37.0).sin();
+        *visibility = if phase > 0.6 {
+            Visibility::Hidden
+        } else {
+            Visibility::Inherited
+        };
+    }
+}
@@ -0,0 +1,224 @@
+//! Records what the player does every tick, so ghosts can do it again.
+//! Ghosts don't follow a recorded path. They get the same input the player
+//! had and move with the same code, which replays the loop exactly, walls,
+//! pauses and all. Input only changes when a key does, so it is stored as
+//! runs of identical ticks.
+
+use bevy::prelude::*;
+
+use super::{
+    assets::{Action, NlaTrack},
+    movement::{apply_movement, IsDead, MovementController, Timeloop},
+    noise::ThrowInput,
+    spawn::player::Player,
+};
+use crate::{screen::InGame, FixedSet};
+
+pub(super) fn plugin(app: &mut App) {
+    app.register_type::<GhostRecording>();
+    app.init_resource::<GhostRecording>();
+    app.add_systems(
+        FixedUpdate,
+        record_player
+            .after(apply_movement)
+            .in_set(FixedSet::Move)
+            .run_if(in_state(InGame)),
+    );
+}
+
+/// Everything the player did, one [`LoopRecording`] per loop, indexed by generation.
+#[derive(Resource, Debug, Clone, Default, Reflect)]
+#[reflect(Resource)]
+pub struct GhostRecording {
+    pub loops: Vec<LoopRecording>,
+}
+
+impl GhostRecording {
+    pub fn new(start: Transform) -> Self {
+        Self {
+            loops: vec![LoopRecording::new(start)],
+        }
+    }
+
+    /// What the player did during `tick` of loop `gen`.
+    pub fn sample(&self, gen: u16, tick: u32) -> Option<TickSample> {
+        self.loops.get(usize::from(gen))?.sample(tick)
+    }
+}
+
+#[derive(Debug, Clone, Default, Reflect)]
+pub struct LoopRecording {
+    /// Where the player was when the loop started.
+    pub start: Transform,
+    /// What the player was carrying when the loop started.
+    pub carrying: Vec<String>,
+    /// The tick each run of identical samples starts at, and the sample.
+    pub runs: Vec<(u32, TickSample)>,
+    /// How many ticks have been recorded.
+    pub ticks: u32,
+}
+
+impl LoopRecording {
+    pub fn new(start: Transform) -> Self {
+        Self {
+            start,
+            carrying: vec![],
+            runs: vec![],
+            ticks: 0,
+        }
+    }
+
+    pub fn push(&mut self, sample: TickSample) {
+        if self.runs.last().map(|(_, last)| *last) != Some(sample) {
+            self.runs.push((self.ticks, sample));
+        }
+        self.ticks += 1;
+    }
+
+    /// Forget everything from `ticks` on, for when the loop is rewound.
+    pub fn truncate(&mut self, ticks: u32) {
+        self.runs.retain(|(start, _)| *start < ticks);
+        self.ticks = self.ticks.min(ticks);
+    }
+
+    /// Forget everything, for when the ghost of this loop is erased.
+    pub fn clear(&mut self) {
+        self.truncate(0);
+    }
+
+    pub fn sample(&self, tick: u32) -> Option<TickSample> {
+        if tick >= self.ticks {
+            return None;
+        }
+        let run = self.runs.partition_point(|(start, _)| *start <= tick);
+        Some(self.runs[run.checked_sub(1)?].1)
+    }
+}
+
+/// What the player did during one tick.
+#[derive(Debug, Clone, Copy, PartialEq, Reflect)]
+pub struct TickSample {
+    /// The [`MovementController`] input.
+    pub input: Vec3,
+    pub track: NlaTrack,
+    /// Whether a pebble was thrown.
+    pub throw: bool,
+}
+
+impl Default for TickSample {
+    fn default() -> Self {
+        Self {
+            input: Vec3::ZERO,
+            track: NlaTrack::Idle,
+            throw: false,
+        }
+    }
+}
+
+fn record_player(
+    timeloop: Res<Timeloop>,
+    throw: Res<ThrowInput>,
+    player: Query<(&MovementController, &Action, Has<IsDead>), With<Player>>,
+    mut recording: ResMut<GhostRecording>,
+) {
+    let Ok((controller, action, is_dead)) = player.get_single() else {
+        return;
+    };
+    let Some(current) = recording.loops.get_mut(usize::from(timeloop.gen)) else {
+        return;
+    };
+    current.push(TickSample {
+        // Dead players don't move, whatever keys are still held.
+        input: if is_dead { Vec3::ZERO } else { controller.0 },
+        track: action.new_track,
+        throw: throw.pressed && !is_dead,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Input that holds each direction for a few ticks, so runs have different lengths.
+    fn sample(tick: u32) -> TickSample {
+        let input = match tick {
+            0..=2 => Vec3::ZERO,
+            3 => Vec3::X,
+            4..=9 => Vec3::Z,
+            _ => Vec3::NEG_X,
+        };
+        TickSample {
+            input,
+            track: if input == Vec3::ZERO { NlaTrack::Idle } else { NlaTrack::Walk },
+            throw: tick == 7,
+        }
+    }
+
+    fn recorded(ticks: u32) -> LoopRecording {
+        let mut recording = LoopRecording::new(Transform::IDENTITY);
+        for tick in 0..ticks {
+            recording.push(sample(tick));
+        }
+        recording
+    }
+
+    #[test]
+    fn every_tick_samples_back_as_recorded() {
+        let recording = recorded(15);
+        assert_eq!(recording.ticks, 15);
+        for tick in 0..15 {
+            assert_eq!(recording.sample(tick), Some(sample(tick)), "tick {tick}");
+        }
+        assert_eq!(recording.sample(15), None);
+    }
+
+    #[test]
+    fn identical_ticks_share_a_run() {
+        let recording = recorded(15);
+        let starts: Vec<u32> = recording.runs.iter().map(|(start, _)| *start).collect();
+        assert_eq!(starts, vec![0, 3, 4, 7, 8, 10]);
+    }
+
+    #[test]
+    fn truncating_in_the_middle_of_a_run() {
+        let mut recording = recorded(15);
+        recording.truncate(6);
+        assert_eq!(recording.ticks, 6);
+        for tick in 0..6 {
+            assert_eq!(recording.sample(tick), Some(sample(tick)), "tick {tick}");
+        }
+        assert_eq!(recording.sample(6), None);
+
+        // Recording again carries on the run it was cut in.
+        recording.push(sample(4));
+        recording.push(sample(12));
+        assert_eq!(recording.sample(6), Some(sample(4)));
+        assert_eq!(recording.sample(7), Some(sample(12)));
+        let starts: Vec<u32> = recording.runs.iter().map(|(start, _)| *start).collect();
+        assert_eq!(starts, vec![0, 3, 4, 7]);
+    }
+
+    #[test]
+    fn truncating_at_a_run_edge_or_past_the_end() {
+        let mut recording = recorded(15);
+        recording.truncate(20);
+        assert_eq!(recording.ticks, 15);
+        recording.truncate(4);
+        assert_eq!(recording.runs.last(), Some(&(3, sample(3))));
+        assert_eq!(recording.sample(3), Some(sample(3)));
+        assert_eq!(recording.sample(4), None);
+        recording.clear();
+        assert_eq!(recording.ticks, 0);
+        assert!(recording.runs.is_empty());
+        assert_eq!(recording.sample(0), None);
+    }
+
+    #[test]
+    fn samples_by_generation() {
+        let mut recording = GhostRecording::new(Transform::IDENTITY);
+        recording.loops.push(recorded(5));
+        assert_eq!(recording.sample(0, 0), None);
+        assert_eq!(recording.sample(1, 3), Some(sample(3)));
+        assert_eq!(recording.sample(2, 0), None);
+    }
+}
@@ -0,0 +1,309 @@
+//! Whole runs recorded as the input of every tick, to be watched again.
+//! Gameplay only depends on the level and on what the player pressed each
+//! tick, so that is all a [`Replay`] stores, rewinds included. Playing one
+//! back feeds the same input into the same systems, so it shows exactly
+//! what happened, including what guards saw in
+//! [`detect_player`](super::guards::detect_player).
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    assets::{HandleMap, LevelKey},
+    campaign::CurrentLevel,
+    ghosts::GhostOrder,
+    level::Level,
+    movement::MovementController,
+    noise::ThrowInput,
+    rewind::{ForgetRewind, Rewind},
+    spawn::player::Player,
+};
+use crate::{screen::Screen, FixedSet};
+
+pub(super) fn plugin(app: &mut App) {
+    app.register_type::<Replay>();
+    app.init_resource::<ReplayRecorder>();
+
+    app.observe(watch_replay);
+    app.observe(record_ghost_order);
+    app.add_systems(OnEnter(Screen::Playing), start_recording);
+    app.add_systems(OnExit(Screen::Playing), write_replay);
+    app.add_systems(
+        FixedUpdate,
+        (
+            record_tick.run_if(in_state(Screen::Playing)),
+            play_tick.run_if(in_state(Screen::Replay).and_then(resource_exists::<ReplayPlayer>)),
+        )
+            .in_set(FixedSet::Input),
+    );
+}
+
+/// Where the last run is written, relative to the working directory.
+pub const REPLAY_FILE: &str = "last.replay.ron";
+
+/// A run from the start of its level, one input per tick.
+#[derive(Debug, Clone, Default, PartialEq, Reflect, Serialize, Deserialize)]
+pub struct Replay {
+    /// The game version that recorded it, since other versions may play it out differently.
+    pub version: String,
+    /// Position in [`CAMPAIGN`](super::campaign::CAMPAIGN), if the run was on a campaign level.
+    pub campaign_index: Option<usize>,
+    /// Seed of the level, if the run was on a generated one.
+    pub seed: Option<u64>,
+    /// The tick each run of identical input starts at, and the input.
+    pub runs: Vec<(u32, ReplayInput)>,
+    /// How many ticks have been recorded.
+    pub ticks: u32,
+    /// Ghost orders, each with the tick they were given just before.
+    #[serde(default)]
+    pub orders: Vec<(u32, GhostOrder)>,
+    /// Ticks a save was loaded just before, which rewinding can't go back past.
+    #[serde(default)]
+    pub loads: Vec<u32>,
+}
+
+/// What the player pressed during one tick.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Reflect, Serialize, Deserialize)]
+pub struct ReplayInput {
+    /// The [`MovementController`] input.
+    pub movement: Vec3,
+    /// Whether rewind was held.
+    pub rewind: bool,
+    /// Whether a pebble was thrown.
+    #[serde(default)]
+    pub throw: bool,
+}
+
+impl Replay {
+    pub fn new(current_level: &CurrentLevel) -> Self {
+        Self {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            campaign_index: current_level.campaign_index,
+            seed: current_level.seed,
+            runs: vec![],
+            ticks: 0,
+            orders: vec![],
+            loads: vec![],
+        }
+    }
+
+    pub fn push(&mut self, input: ReplayInput) {
+        if self.runs.last().map(|(_, last)| *last) != Some(input) {
+            self.runs.push((self.ticks, input));
+        }
+        self.ticks += 1;
+    }
+
+    pub fn input(&self, tick: u32) -> Option<ReplayInput> {
+        if tick >= self.ticks {
+            return None;
+        }
+        let run = self.runs.partition_point(|(start, _)| *start <= tick);
+        Some(self.runs[run.checked_sub(1)?].1)
+    }
+}
+
+/// The run being played, as it is being recorded.
+#[derive(Resource, Debug, Default)]
+pub struct ReplayRecorder(pub Replay);
+
+/// The replay being watched, and how far into it we are.
+#[derive(Resource, Debug)]
+pub struct ReplayPlayer {
+    pub replay: Replay,
+    pub tick: u32,
+}
+
+impl ReplayPlayer {
+    pub fn is_finished(&self) -> bool {
+        self.tick >= self.replay.ticks
+    }
+}
+
+/// Read [`REPLAY_FILE`] and start watching it.
+#[derive(Event, Debug)]
+pub struct WatchReplay;
+
+fn start_recording(current_level: Res<CurrentLevel>, mut recorder: ResMut<ReplayRecorder>) {
+    recorder.0 = Replay::new(&current_level);
+}
+
+fn record_tick(
+    rewind: Res<Rewind>,
+    throw: Res<ThrowInput>,
+    player: Query<&MovementController, With<Player>>,
+    mut recorder: ResMut<ReplayRecorder>,
+) {
+    recorder.0.push(ReplayInput {
+        movement: player.get_single().map_or(Vec3::ZERO, |controller| controller.0),
+        rewind: rewind.held,
+        throw: throw.pressed,
+    });
+}
+
+/// Orders are given while the game is paused, between two ticks,
+/// so replays give them at the start of the next one.
+fn record_ghost_order(
+    trigger: Trigger<GhostOrder>,
+    screen: Res<State<Screen>>,
+    mut recorder: ResMut<ReplayRecorder>,
+) {
+    if *screen.get() == Screen::Playing {
+        let tick = recorder.0.ticks;
+        recorder.0.orders.push((tick, *trigger.event()));
+    }
+}
+
+fn play_tick(
+    mut replay_player: ResMut<ReplayPlayer>,
+    mut rewind: ResMut<Rewind>,
+    mut throw: ResMut<ThrowInput>,
+    mut player: Query<&mut MovementController, With<Player>>,
+    mut time: ResMut<Time<Virtual>>,
+    mut commands: Commands,
+) {
+    let input = replay_player
+        .replay
+        .input(replay_player.tick)
+        .unwrap_or_default();
+    rewind.held = input.rewind;
+    throw.pressed = input.throw;
+    for mut controller in &mut player {
+        controller.0 = input.movement;
+    }
+    let tick = replay_player.tick;
+    for &(_, order) in replay_player
+        .replay
+        .orders
+        .iter()
+        .filter(|(given, _)| *given == tick)
+    {
+        commands.trigger(order);
+    }
+    // Loading started rewinding over, so it does here too.
+    if replay_player.replay.loads.contains(&tick) {
+        commands.trigger(ForgetRewind);
+    }
+    if replay_player.is_finished() {
+        return;
+    }
+    replay_player.tick += 1;
+    // Stop on the last tick, the viewer can still carry on from there.
+    if replay_player.is_finished() {
+        time.pause();
+    }
+}
+
+fn write_replay(current_level: Res<CurrentLevel>, recorder: Res<ReplayRecorder>) {
+    if recorder.0.ticks == 0 || !current_level.is_restorable() {
+        return;
+    }
+    let text = match ron::ser::to_string(&recorder.0) {
+        Ok(text) => text,
+        Err(error) => {
+            error!("Could not serialize replay: {error}");
+            return;
+        }
+    };
+    match write_replay_file(&text) {
+        Ok(()) => info!("Saved replay to {REPLAY_FILE}"),
+        Err(error) => error!("Could not save replay to {REPLAY_FILE}: {error}"),
+    }
+}
+
+fn watch_replay(
+    _trigger: Trigger<WatchReplay>,
+    level_handles: Res<HandleMap<LevelKey>>,
+    mut levels: ResMut<Assets<Level>>,
+    mut next_screen: ResMut<NextState<Screen>>,
+    mut commands: Commands,
+) {
+    let replay: Replay = match read_replay_file() {
+        Ok(text) => match ron::de::from_str(&text) {
+            Ok(replay) => replay,
+            Err(error) => {
+                error!("Could not parse {REPLAY_FILE}: {error}");
+                return;
+            }
+        },
+        Err(error) => {
+            warn!("Could not read {REPLAY_FILE}: {error}");
+            return;
+        }
+    };
+    if replay.version != env!("CARGO_PKG_VERSION") {
+        warn!(
+            "{REPLAY_FILE} was recorded by version {}, it may not play out the same",
+            replay.version
+        );
+    }
+    let Some(current_level) =
+        CurrentLevel::restore(replay.campaign_index, replay.seed, &level_handles, &mut levels)
+    else {
+        error!("{REPLAY_FILE} is for a level that no longer exists");
+        return;
+    };
+    commands.insert_resource(current_level);
+    commands.insert_resource(ReplayPlayer { replay, tick: 0 });
+    next_screen.set(Screen::Replay);
+}
+
+#[cfg(not(target_family = "wasm"))]
+fn write_replay_file(text: &str) -> std::io::Result<()> {
+    std::fs::write(REPLAY_FILE, text)
+}
+
+#[cfg(not(target_family = "wasm"))]
+fn read_replay_file() -> std::io::Result<String> {
+    std::fs::read_to_string(REPLAY_FILE)
+}
+
+#[cfg(target_family = "wasm")]
+fn write_replay_file(_text: &str) -> std::io::Result<()> {
+    Err(std::io::ErrorKind::Unsupported.into())
+}
+
+#[cfg(target_family = "wasm")]
+fn read_replay_file() -> std::io::Result<String> {
+    Err(std::io::ErrorKind::Unsupported.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn input(tick: u32) -> ReplayInput {
+        ReplayInput {
+            movement: if tick < 5 { Vec3::X } else { Vec3::ZERO },
+            rewind: (8..10).contains(&tick),
+            throw: tick == 3,
+        }
+    }
+
+    fn recorded(ticks: u32) -> Replay {
+        let mut replay = Replay::default();
+        for tick in 0..ticks {
+            replay.push(input(tick));
+        }
+        replay
+    }
+
+    #[test]
+    fn every_tick_plays_back_as_recorded() {
+        let replay = recorded(12);
+        for tick in 0..12 {
+            assert_eq!(replay.input(tick), Some(input(tick)), "tick {tick}");
+        }
+        assert_eq!(replay.input(12), None);
+        let starts: Vec<u32> = replay.runs.iter().map(|(start, _)| *start).collect();
+        assert_eq!(starts, vec![0, 3, 4, 5, 8, 10]);
+    }
+
+    #[test]
+    fn survives_being_written_out() {
+        let mut replay = recorded(12);
+        replay.loads.push(6);
+        let text = ron::ser::to_string(&replay).unwrap();
+        assert_eq!(ron::de::from_str::<Replay>(&text).unwrap(), replay);
+    }
+}
@@ -0,0 +1,311 @@
+//! Hold a key to rewind the current loop by a few seconds.
+//! Every tick the state that isn't a pure function of time is saved into a
+//! ring buffer: where everyone stands, who is dead, what they carry, what
+//! guards are up to and which bodies they found, what hounds can smell, how
+//! hot the furnaces are and which doors, switches and items have been used.
+//! Rewinding pauses the rest of gameplay and walks back through it.
+//! The buffer only covers the current loop, since ghosts are only created
+//! when a loop ends.
+
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+
+use super::{
+    assets::{Action, NlaTrack},
+    guards::{Alarm, Discovered, GuardAi},
+    movement::{Ghost, IsDead, IsGoingToHell, Npc, Timeloop, TICK_RATE},
+    paradox::KilledBy,
+    recording::GhostRecording,
+    scent::Scent,
+    spawn::{player::Player, stage::Furnace},
+    world::{Carrying, WorldObject},
+};
+use crate::{
+    screen::{InGame, Screen},
+    AppSet, FixedSet,
+};
+
+pub(super) fn plugin(app: &mut App) {
+    app.register_type::<Rewind>();
+    app.init_resource::<Rewind>();
+    app.init_resource::<RewindBuffer>();
+
+    app.observe(forget_rewind);
+    app.add_systems(OnEnter(InGame), clear_buffer);
+    app.add_systems(
+        Update,
+        record_rewind_input
+            .in_set(AppSet::RecordInput)
+            .run_if(in_state(Screen::Playing)),
+    );
+    // Everything else holds still while time runs backwards.
+    app.configure_sets(
+        FixedUpdate,
+        (FixedSet::Move, FixedSet::React, FixedSet::Tick).run_if(not(is_rewinding)),
+    );
+    app.add_systems(
+        FixedUpdate,
+        (
+            rewind
+                .after(FixedSet::Input)
+                .before(FixedSet::Move)
+                .run_if(is_rewinding),
+            take_snapshot
+                .after(FixedSet::Tick)
+                .run_if(not(is_rewinding)),
+        )
+            .run_if(in_state(InGame)),
+    );
+}
+
+/// How far back the player can go.
+const REWIND_SECONDS: u32 = 5;
+/// How many ticks the buffer can go back, on top of the present.
+const REWIND_TICKS: u32 = REWIND_SECONDS * TICK_RATE;
+/// Ticks undone per tick of rewinding.
+const REWIND_SPEED: usize = 2;
+
+#[derive(Resource, Debug, Clone, Copy, Default, Reflect)]
+#[reflect(Resource)]
+pub struct Rewind {
+    pub held: bool,
+}
+
+/// Forget everything rewinding could go back to, like loading a save does.
+#[derive(Event, Debug)]
+pub struct ForgetRewind;
+
+pub fn is_rewinding(rewind: Res<Rewind>) -> bool {
+    rewind.held
+}
+
+#[derive(Resource, Debug, Default)]
+struct RewindBuffer {
+    snapshots: VecDeque<Snapshot>,
+}
+
+impl RewindBuffer {
+    /// Saves the present, forgetting whatever is now further back than [`REWIND_TICKS`].
+    fn push(&mut self, snapshot: Snapshot) {
+        if self.snapshots.back().is_some_and(|last| last.gen != snapshot.gen) {
+            self.snapshots.clear();
+        }
+        // One snapshot for the present, and one for each tick that can be undone.
+        while self.snapshots.len() > REWIND_TICKS as usize {
+            self.snapshots.pop_front();
+        }
+        self.snapshots.push_back(snapshot);
+    }
+}
+
+/// The state of the stage at the end of one tick.
+#[derive(Debug)]
+struct Snapshot {
+    gen: u16,
+    tick: u32,
+    characters: Vec<CharacterState>,
+    furnaces: Vec<(Entity, f32)>,
+    world_objects: Vec<(Entity, bool)>,
+    alarm: Alarm,
+    scent: Scent,
+}
+
+#[derive(Debug)]
+struct CharacterState {
+    entity: Entity,
+    transform: Transform,
+    track: NlaTrack,
+    is_dead: bool,
+    killed_by: Option<KilledBy>,
+    /// Guards don't carry anything.
+    carrying: Option<Vec<String>>,
+    /// Only guards think.
+    ai: Option<GuardAi>,
+    discovered: bool,
+}
+
+fn clear_buffer(mut buffer: ResMut<RewindBuffer>) {
+    buffer.snapshots.clear();
+}
+
+fn forget_rewind(_trigger: Trigger<ForgetRewind>, mut buffer: ResMut<RewindBuffer>) {
+    buffer.snapshots.clear();
+}
+
+fn record_rewind_input(
+    input: Res<ButtonInput<KeyCode>>,
+    alive: Query<(), (With<Player>, Without<IsGoingToHell>)>,
+    mut rewind: ResMut<Rewind>,
+) {
+    // No taking back the shot that killed you.
+    rewind.held = input.pressed(KeyCode::KeyR) && !alive.is_empty();
+}
+
+fn take_snapshot(
+    timeloop: Res<Timeloop>,
+    characters: Query<
+        (
+            Entity,
+            &Transform,
+            &Action,
+            Has<IsDead>,
+            Option<&KilledBy>,
+            Option<&Carrying>,
+            Option<&GuardAi>,
+            Has<Discovered>,
+        ),
+        Or<(With<Player>, With<Npc>, With<Ghost>)>,
+    >,
+    furnaces: Query<(Entity, &Furnace)>,
+    world_objects: Query<(Entity, &WorldObject)>,
+    alarm: Res<Alarm>,
+    scent: Res<Scent>,
+    mut buffer: ResMut<RewindBuffer>,
+) {
+    let snapshot = Snapshot {
+        gen: timeloop.gen,
+        tick: timeloop.tick,
+        characters: characters
+            .iter()
+            .map(
+                |(entity, transform, action, is_dead, killed_by, carrying, ai, discovered)| {
+                    CharacterState {
+                        entity,
+                        transform: *transform,
+                        track: action.new_track,
+                        is_dead,
+                        killed_by: killed_by.copied(),
+                        carrying: carrying.map(|carrying| carrying.0.clone()),
+                        ai: ai.copied(),
+                        discovered,
+                    }
+                },
+            )
+            .collect(),
+        furnaces: furnaces
+            .iter()
+            .map(|(entity, furnace)| (entity, furnace.countdown))
+            .collect(),
+        world_objects: world_objects
+            .iter()
+            .map(|(entity, object)| (entity, object.used))
+            .collect(),
+        alarm: *alarm,
+        scent: scent.clone(),
+    };
+    buffer.push(snapshot);
+}
+
+fn rewind(
+    mut buffer: ResMut<RewindBuffer>,
+    mut timeloop: ResMut<Timeloop>,
+    mut recording: ResMut<GhostRecording>,
+    mut characters: Query<(
+        &mut Transform,
+        &mut Action,
+        Option<&mut Carrying>,
+        Option<&mut GuardAi>,
+    )>,
+    mut furnaces: Query<&mut Furnace>,
+    mut world_objects: Query<&mut WorldObject>,
+    mut alarm: ResMut<Alarm>,
+    mut scent: ResMut<Scent>,
+    mut commands: Commands,
+) {
+    // The newest snapshot is the present, so always keep one to stand on.
+    for _ in 0..REWIND_SPEED {
+        if buffer.snapshots.len() > 1 {
+            buffer.snapshots.pop_back();
+        }
+    }
+    let Some(snapshot) = buffer.snapshots.back() else {
+        return;
+    };
+
+    timeloop.tick = snapshot.tick;
+    *alarm = snapshot.alarm;
+    scent.clone_from(&snapshot.scent);
+    if let Some(current) = recording.loops.get_mut(usize::from(snapshot.gen)) {
+        current.truncate(snapshot.tick);
+    }
+    for state in &snapshot.characters {
+        // Collapsed ghosts stay gone.
+        let Ok((mut transform, mut action, carrying, ai)) = characters.get_mut(state.entity) else {
+            continue;
+        };
+        *transform = state.transform;
+        action.new_track = state.track;
+        if let (Some(mut carrying), Some(carried)) = (carrying, &state.carrying) {
+            carrying.0.clone_from(carried);
+        }
+        if let (Some(mut ai), Some(thought)) = (ai, state.ai) {
+            *ai = thought;
+        }
+        let mut entity = commands.entity(state.entity);
+        if state.is_dead {
+            entity.insert(IsDead);
+        } else {
+            entity.remove::<IsDead>();
+        }
+        match state.killed_by {
+            Some(killed_by) => entity.insert(killed_by),
+            None => entity.remove::<KilledBy>(),
+        };
+        if state.discovered {
+            entity.insert(Discovered);
+        } else {
+            entity.remove::<Discovered>();
+        }
+    }
+    for &(entity, countdown) in &snapshot.furnaces {
+        if let Ok(mut furnace) = furnaces.get_mut(entity) {
+            furnace.countdown = countdown;
+        }
+    }
+    for &(entity, used) in &snapshot.world_objects {
+        if let Ok(mut object) = world_objects.get_mut(entity) {
+            // Only write real changes, doors react to `Changed<WorldObject>`.
+            object.set_if_neq(WorldObject { used, ..*object });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(gen: u16, tick: u32) -> Snapshot {
+        Snapshot {
+            gen,
+            tick,
+            characters: vec![],
+            furnaces: vec![],
+            world_objects: vec![],
+            alarm: Alarm::default(),
+            scent: Scent::default(),
+        }
+    }
+
+    #[test]
+    fn a_full_buffer_goes_back_the_whole_way() {
+        let mut buffer = RewindBuffer::default();
+        for tick in 0..REWIND_TICKS * 3 {
+            buffer.push(snapshot(0, tick));
+        }
+        let (oldest, newest) = (&buffer.snapshots[0], buffer.snapshots.back().unwrap());
+        assert_eq!(newest.tick - oldest.tick, REWIND_TICKS);
+        assert_eq!(buffer.snapshots.len(), REWIND_TICKS as usize + 1);
+    }
+
+    #[test]
+    fn a_new_loop_starts_an_empty_buffer() {
+        let mut buffer = RewindBuffer::default();
+        for tick in 0..10 {
+            buffer.push(snapshot(0, tick));
+        }
+        buffer.push(snapshot(1, 0));
+        assert_eq!(buffer.snapshots.len(), 1);
+        assert_eq!(buffer.snapshots[0].gen, 1);
+    }
+}
@@ -0,0 +1,365 @@
+//! Saving a run to disk and picking it up again.
+//! A save only stores what can't be rebuilt from the level: where the loop
+//! is, everything the player recorded, who is dead and where everyone
+//! stands. It is written through the registered [`Reflect`] types, so
+//! anything reflected here ends up in the file without extra serde code.
+//! Loading goes back through [`Screen::Loading`], which builds the stage
+//! again before [`PendingLoad`] is put on top of it.
+
+use bevy::{
+    reflect::serde::{ReflectDeserializer, ReflectSerializer},
+    prelude::*,
+};
+use serde::de::DeserializeSeed;
+
+use super::{
+    assets::{Action, HandleMap, LevelKey, NlaTrack, SceneKey},
+    campaign::CurrentLevel,
+    ghosts::Locked,
+    guards::{Alarm, Discovered, GuardAi},
+    interpolation::Interpolated,
+    level::Level,
+    movement::{ghost_bundle, Ghost, IsDead, IsGoingToHell, Movement, Npc, Timeloop},
+    paradox::KilledBy,
+    recording::GhostRecording,
+    replay::{Replay, ReplayRecorder},
+    rewind::is_rewinding,
+    scent::Scent,
+    spawn::{player::Player, stage::Furnace},
+    world::{Carrying, WorldObject},
+};
+use crate::{screen::Screen, FixedSet};
+
+pub(super) fn plugin(app: &mut App) {
+    app.register_type::<SaveGame>();
+
+    app.observe(save_run);
+    app.observe(load_run);
+    app.add_systems(
+        FixedUpdate,
+        (
+            // Before anything else, so the first tick already runs on the save.
+            apply_pending_load
+                .before(FixedSet::Input)
+                .run_if(resource_exists::<PendingLoad>),
+            autosave.after(FixedSet::Tick).run_if(not(is_rewinding)),
+        )
+            .run_if(in_state(Screen::Playing)),
+    );
+}
+
+/// Where the run is saved, relative to the working directory.
+pub const SAVE_FILE: &str = "savegame.ron";
+
+/// Write the current run to [`SAVE_FILE`].
+#[derive(Event, Debug)]
+pub struct SaveRun;
+
+/// Read [`SAVE_FILE`] and continue the run from there.
+#[derive(Event, Debug)]
+pub struct LoadRun;
+
+/// Everything needed to continue a run.
+#[derive(Debug, Clone, Reflect)]
+pub struct SaveGame {
+    /// Position in [`CAMPAIGN`](super::campaign::CAMPAIGN), if the run is on a campaign level.
+    pub campaign_index: Option<usize>,
+    /// Seed of the level, if the run is on a generated one.
+    pub seed: Option<u64>,
+    pub timeloop: Timeloop,
+    pub recording: GhostRecording,
+    pub player: Transform,
+    pub player_carrying: Vec<String>,
+    pub ghosts: Vec<GhostSave>,
+    pub guards: Vec<GuardSave>,
+    pub furnaces: Vec<FurnaceSave>,
+    pub world: Vec<WorldSave>,
+    pub alarm: Alarm,
+    pub scent: Scent,
+    /// The run so far, so its replay goes on from where it was saved.
+    pub replay: Replay,
+}
+
+#[derive(Debug, Clone, Reflect)]
+pub struct GhostSave {
+    pub gen: u16,
+    pub transform: Transform,
+    pub is_dead: bool,
+    pub locked: bool,
+    pub carrying: Vec<String>,
+}
+
+/// Guards and furnaces are matched by name, which levels keep unique.
+#[derive(Debug, Clone, Reflect)]
+pub struct GuardSave {
+    pub name: String,
+    pub transform: Transform,
+    pub is_dead: bool,
+    pub killed_by: Option<KilledBy>,
+    pub ai: GuardAi,
+    pub discovered: bool,
+}
+
+#[derive(Debug, Clone, Reflect)]
+pub struct FurnaceSave {
+    pub name: String,
+    pub countdown: f32,
+}
+
+/// Doors, switches and items, matched by name like guards.
+#[derive(Debug, Clone, Reflect)]
+pub struct WorldSave {
+    pub name: String,
+    pub used: bool,
+}
+
+/// A loaded save, waiting for its level to be spawned.
+#[derive(Resource, Debug)]
+pub struct PendingLoad(pub SaveGame);
+
+fn save_run(
+    _trigger: Trigger<SaveRun>,
+    registry: Res<AppTypeRegistry>,
+    current_level: Res<CurrentLevel>,
+    timeloop: Res<Timeloop>,
+    recording: Res<GhostRecording>,
+    recorder: Res<ReplayRecorder>,
+    player: Query<(&Transform, &Carrying), (With<Player>, Without<IsGoingToHell>)>,
+    ghosts: Query<(&Ghost, &Transform, Has<IsDead>, Has<Locked>, &Carrying)>,
+    guards: Query<
+        (&Name, &Transform, Has<IsDead>, Option<&KilledBy>, &GuardAi, Has<Discovered>),
+        With<Npc>,
+    >,
+    furnaces: Query<(&Name, &Furnace)>,
+    world_objects: Query<(&Name, &WorldObject)>,
+    alarm: Res<Alarm>,
+    scent: Res<Scent>,
+) {
+    if !current_level.is_restorable() {
+        warn!("Only campaign and generated levels can be saved");
+        return;
+    }
+    // Nothing worth continuing from.
+    let Ok((player, player_carrying)) = player.get_single() else {
+        return;
+    };
+    let save = SaveGame {
+        campaign_index: current_level.campaign_index,
+        seed: current_level.seed,
+        timeloop: timeloop.clone(),
+        recording: recording.clone(),
+        player: *player,
+        player_carrying: player_carrying.0.clone(),
+        ghosts: ghosts
+            .iter()
+            .map(|(ghost, transform, is_dead, locked, carrying)| GhostSave {
+                gen: ghost.gen,
+                transform: *transform,
+                is_dead,
+                locked,
+                carrying: carrying.0.clone(),
+            })
+            .collect(),
+        guards: guards
+            .iter()
+            .map(|(name, transform, is_dead, killed_by, ai, discovered)| GuardSave {
+                name: name.to_string(),
+                transform: *transform,
+                is_dead,
+                killed_by: killed_by.copied(),
+                ai: *ai,
+                discovered,
+            })
+            .collect(),
+        furnaces: furnaces
+            .iter()
+            .map(|(name, furnace)| FurnaceSave {
+                name: name.to_string(),
+                countdown: furnace.countdown,
+            })
+            .collect(),
+        world: world_objects
+            .iter()
+            .map(|(name, object)| WorldSave {
+                name: name.to_string(),
+                used: object.used,
+            })
+            .collect(),
+        alarm: *alarm,
+        scent: scent.clone(),
+        replay: recorder.0.clone(),
+    };
+
+    let registry = registry.read();
+    let serializer = ReflectSerializer::new(&save, &registry);
+    let text = match ron::ser::to_string_pretty(&serializer, default()) {
+        Ok(text) => text,
+        Err(error) => {
+            error!("Could not serialize save: {error}");
+            return;
+        }
+    };
+    match write_save_file(&text) {
+        Ok(()) => info!("Saved run to {SAVE_FILE}"),
+        Err(error) => error!("Could not save run to {SAVE_FILE}: {error}"),
+    }
+}
+
+fn load_run(
+    _trigger: Trigger<LoadRun>,
+    registry: Res<AppTypeRegistry>,
+    level_handles: Res<HandleMap<LevelKey>>,
+    mut levels: ResMut<Assets<Level>>,
+    mut next_screen: ResMut<NextState<Screen>>,
+    mut commands: Commands,
+) {
+    let text = match read_save_file() {
+        Ok(text) => text,
+        Err(error) => {
+            warn!("Could not read {SAVE_FILE}: {error}");
+            return;
+        }
+    };
+
+    let save = match parse_save(&text, &registry.read()) {
+        Ok(save) => save,
+        Err(error) => {
+            error!("Could not load {SAVE_FILE}: {error}");
+            return;
+        }
+    };
+    let Some(current_level) =
+        CurrentLevel::restore(save.campaign_index, save.seed, &level_handles, &mut levels)
+    else {
+        error!("{SAVE_FILE} is for a level that no longer exists");
+        return;
+    };
+    commands.insert_resource(current_level);
+    commands.insert_resource(PendingLoad(save));
+    next_screen.set(Screen::Loading);
+}
+
+#[cfg(not(target_family = "wasm"))]
+fn write_save_file(text: &str) -> std::io::Result<()> {
+    std::fs::write(SAVE_FILE, text)
+}
+
+#[cfg(not(target_family = "wasm"))]
+fn read_save_file() -> std::io::Result<String> {
+    std::fs::read_to_string(SAVE_FILE)
+}
+
+#[cfg(target_family = "wasm")]
+fn write_save_file(_text: &str) -> std::io::Result<()> {
+    Err(std::io::ErrorKind::Unsupported.into())
+}
+
+#[cfg(target_family = "wasm")]
+fn read_save_file() -> std::io::Result<String> {
+    Err(std::io::ErrorKind::Unsupported.into())
+}
+
+fn parse_save(text: &str, registry: &bevy::reflect::TypeRegistry) -> Result<SaveGame, String> {
+    let mut deserializer = ron::de::Deserializer::from_str(text).map_err(|error| error.to_string())?;
+    let reflected = ReflectDeserializer::new(registry)
+        .deserialize(&mut deserializer)
+        .map_err(|error| error.to_string())?;
+    SaveGame::from_reflect(&*reflected).ok_or_else(|| "not a save file".to_string())
+}
+
+/// Save whenever a new loop starts, so dying only loses the current one.
+fn autosave(
+    timeloop: Res<Timeloop>,
+    player: Query<(), (With<Player>, Without<IsDead>)>,
+    mut commands: Commands,
+) {
+    if timeloop.tick == 0 && timeloop.gen > 0 && !player.is_empty() {
+        commands.trigger(SaveRun);
+    }
+}
+
+/// Put the saved run on top of the freshly spawned stage.
+fn apply_pending_load(
+    pending: Res<PendingLoad>,
+    scene_handles: Res<HandleMap<SceneKey>>,
+    mut timeloop: ResMut<Timeloop>,
+    mut recording: ResMut<GhostRecording>,
+    mut recorder: ResMut<ReplayRecorder>,
+    mut alarm: ResMut<Alarm>,
+    mut scent: ResMut<Scent>,
+    mut player: Query<(&mut Transform, &mut Interpolated, &mut Carrying, &Movement), With<Player>>,
+    mut guards: Query<
+        (Entity, &Name, &mut Transform, &mut Interpolated, &mut Action, &mut GuardAi),
+        (With<Npc>, Without<Player>),
+    >,
+    mut furnaces: Query<(&Name, &mut Furnace)>,
+    mut world_objects: Query<(&Name, &mut WorldObject)>,
+    mut commands: Commands,
+) {
+    let Ok((mut player_transform, mut player_interpolated, mut player_carrying, movement)) =
+        player.get_single_mut()
+    else {
+        return;
+    };
+    let save = &pending.0;
+    *timeloop = save.timeloop.clone();
+    *recording = save.recording.clone();
+    recorder.0 = save.replay.clone();
+    // Entering the stage again forgot everything rewinding could go back to.
+    let tick = recorder.0.ticks;
+    recorder.0.loads.push(tick);
+    *alarm = save.alarm;
+    scent.clone_from(&save.scent);
+    *player_transform = save.player;
+    *player_interpolated = Interpolated::new(save.player);
+    player_carrying.0.clone_from(&save.player_carrying);
+
+    for ghost in &save.ghosts {
+        let mut entity = commands.spawn(ghost_bundle(
+            &scene_handles,
+            ghost.transform,
+            movement.clone(),
+            ghost.gen,
+            ghost.carrying.clone(),
+        ));
+        if ghost.is_dead {
+            entity.insert(IsDead);
+            entity.insert(Action {
+                current_track: NlaTrack::Idle,
+                new_track: NlaTrack::Die,
+            });
+        }
+        if ghost.locked {
+            entity.insert(Locked);
+        }
+    }
+    for (entity, name, mut transform, mut interpolated, mut action, mut ai) in &mut guards {
+        let Some(guard) = save.guards.iter().find(|guard| guard.name == name.as_str()) else {
+            continue;
+        };
+        *transform = guard.transform;
+        *interpolated = Interpolated::new(guard.transform);
+        *ai = guard.ai;
+        if guard.is_dead {
+            commands.entity(entity).insert(IsDead);
+            action.new_track = NlaTrack::Die;
+        }
+        if let Some(killed_by) = guard.killed_by {
+            commands.entity(entity).insert(killed_by);
+        }
+        if guard.discovered {
+            commands.entity(entity).insert(Discovered);
+        }
+    }
+    for (name, mut furnace) in &mut furnaces {
+        if let Some(saved) = save.furnaces.iter().find(|saved| saved.name == name.as_str()) {
+            furnace.countdown = saved.countdown;
+        }
+    }
+    for (name, mut object) in &mut world_objects {
+        if let Some(saved) = save.world.iter().find(|saved| saved.name == name.as_str()) {
+            object.used = saved.used;
+        }
+    }
+    commands.remove_resource::<PendingLoad>();
+}
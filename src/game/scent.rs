@@ -0,0 +1,121 @@
+//! Trails hounds follow.
+//! The player and ghosts leave a mark where they stand every so often, and
+//! marks fade after a while. A hound that smells marks goes to the freshest
+//! one around it, which leads it along the trail to whoever left it. Smell
+//! goes around walls, and doesn't care how dark it is.
+//! Marks are part of the loop like everything else, so rewinding and saves keep them.
+
+use bevy::prelude::*;
+
+use super::{
+    guards::{detect_player, GuardAi, GuardKind},
+    movement::{Ghost, IsDead, Npc, Timeloop, TICK_RATE},
+    spawn::player::Player,
+    vision::VisionDebug,
+    world::LoopStarted,
+};
+use crate::{screen::InGame, FixedSet};
+
+pub(super) fn plugin(app: &mut App) {
+    app.register_type::<Scent>();
+    app.init_resource::<Scent>();
+
+    app.observe(clear_scent);
+    app.add_systems(
+        FixedUpdate,
+        (leave_scent, follow_scent)
+            .chain()
+            .before(detect_player)
+            .in_set(FixedSet::React)
+            .run_if(in_state(InGame)),
+    );
+    app.add_systems(Update, draw_scent.run_if(in_state(InGame)));
+}
+
+/// Ticks between two marks.
+const MARK_TICKS: u32 = TICK_RATE / 4;
+/// Ticks a mark lasts.
+const MARK_LIFETIME: u32 = 10 * TICK_RATE;
+/// How far away hounds smell a mark.
+const SMELL_RADIUS: f32 = 4.0;
+
+#[derive(Resource, Debug, Clone, Default, PartialEq, Reflect)]
+#[reflect(Resource)]
+pub struct Scent {
+    pub marks: Vec<ScentMark>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Reflect)]
+pub struct ScentMark {
+    pub position: Vec3,
+    /// Tick of the loop it was left at.
+    pub tick: u32,
+}
+
+fn clear_scent(_trigger: Trigger<LoopStarted>, mut scent: ResMut<Scent>) {
+    scent.marks.clear();
+}
+
+fn leave_scent(
+    timeloop: Res<Timeloop>,
+    characters: Query<&Transform, (Or<(With<Player>, With<Ghost>)>, Without<IsDead>)>,
+    mut scent: ResMut<Scent>,
+) {
+    scent
+        .marks
+        .retain(|mark| mark.tick + MARK_LIFETIME > timeloop.tick);
+    if timeloop.tick % MARK_TICKS != 0 {
+        return;
+    }
+    for transform in &characters {
+        scent.marks.push(ScentMark {
+            position: transform.translation,
+            tick: timeloop.tick,
+        });
+    }
+}
+
+fn follow_scent(
+    scent: Res<Scent>,
+    mut hounds: Query<(&Transform, &GuardKind, &mut GuardAi), (With<Npc>, Without<IsDead>)>,
+) {
+    for (transform, kind, mut ai) in &mut hounds {
+        if !kind.profile().tracks_scent {
+            continue;
+        }
+        let distance = |mark: &ScentMark| mark.position.xz().distance(transform.translation.xz());
+        // Marks from the same tick go by distance, whatever order they were left in.
+        let freshest = scent
+            .marks
+            .iter()
+            .filter(|mark| distance(mark) < SMELL_RADIUS)
+            .max_by(|a, b| {
+                a.tick
+                    .cmp(&b.tick)
+                    .then(distance(b).total_cmp(&distance(a)))
+            });
+        if let Some(mark) = freshest {
+            ai.hear(mark.position);
+        }
+    }
+}
+
+fn draw_scent(
+    debug: Res<VisionDebug>,
+    timeloop: Res<Timeloop>,
+    scent: Res<Scent>,
+    mut gizmos: Gizmos,
+) {
+    if !debug.0 {
+        return;
+    }
+    for mark in &scent.marks {
+        let age = timeloop.tick.saturating_sub(mark.tick) as f32 / MARK_LIFETIME as f32;
+        gizmos.circle(
+            mark.position.with_y(0.05),
+            Dir3::Y,
+            0.15,
+            Color::srgba(0.8, 0.6, 0.2, 1.0 - age),
+        );
+    }
+}
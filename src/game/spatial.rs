@@ -0,0 +1,236 @@
+//! Finding what is near something without going through everything.
+//! Walls and characters are sorted into a uniform grid of square cells once
+//! per tick, and queries only look at the cells they touch. Walls are sorted
+//! before anyone moves, so doors opened last tick count, and characters once
+//! everyone has moved, for everything that reacts to where they ended up.
+//! Queries hand things back in the order they were added, so gameplay goes
+//! the same as it would going through everything.
+
+use bevy::{prelude::*, utils::HashMap};
+
+use super::{
+    collision::{Collider, Obstacle},
+    movement::{Ghost, Npc},
+    spawn::player::Player,
+    world::sync_doors,
+};
+use crate::{screen::InGame, FixedSet};
+
+pub(super) fn plugin(app: &mut App) {
+    app.init_resource::<WallIndex>();
+    app.init_resource::<CharacterIndex>();
+
+    app.add_systems(
+        FixedUpdate,
+        (
+            index_walls
+                .after(FixedSet::Input)
+                .after(sync_doors)
+                .before(FixedSet::Move),
+            index_characters
+                .after(FixedSet::Move)
+                .before(FixedSet::React),
+        )
+            .run_if(in_state(InGame)),
+    );
+}
+
+/// Side of a cell. About as big as a room's worth of walls, and as far as characters reach.
+pub const CELL: f32 = 4.0;
+
+/// Things sorted into square cells by the rectangle they cover on the floor.
+#[derive(Debug, Clone)]
+pub struct SpatialGrid<T> {
+    items: Vec<T>,
+    /// Indices into `items`, for every cell something covers.
+    cells: HashMap<IVec2, Vec<usize>>,
+}
+
+impl<T> Default for SpatialGrid<T> {
+    fn default() -> Self {
+        Self {
+            items: vec![],
+            cells: HashMap::default(),
+        }
+    }
+}
+
+impl<T> SpatialGrid<T> {
+    pub fn clear(&mut self) {
+        self.items.clear();
+        self.cells.clear();
+    }
+
+    pub fn insert(&mut self, min: Vec2, max: Vec2, item: T) {
+        let index = self.items.len();
+        self.items.push(item);
+        for cell in cells(min, max) {
+            self.cells.entry(cell).or_default().push(index);
+        }
+    }
+
+    pub fn insert_point(&mut self, point: Vec2, item: T) {
+        self.insert(point, point, item);
+    }
+
+    /// Everything, in the order it was added.
+    pub fn items(&self) -> &[T] {
+        &self.items
+    }
+
+    /// Everything that may cover part of `min` to `max`, in the order it was added.
+    /// Whatever is left out certainly doesn't.
+    pub fn query(&self, min: Vec2, max: Vec2) -> Vec<&T> {
+        let mut indices: Vec<usize> = cells(min, max)
+            .filter_map(|cell| self.cells.get(&cell))
+            .flatten()
+            .copied()
+            .collect();
+        indices.sort_unstable();
+        indices.dedup();
+        indices.into_iter().map(|index| &self.items[index]).collect()
+    }
+
+    /// Everything that may be within `radius` of `center`.
+    pub fn near(&self, center: Vec2, radius: f32) -> Vec<&T> {
+        self.query(center - radius, center + radius)
+    }
+
+    /// Everything that may be in the way between `a` and `b`.
+    pub fn along(&self, a: Vec2, b: Vec2) -> Vec<&T> {
+        self.query(a.min(b), a.max(b))
+    }
+}
+
+fn cells(min: Vec2, max: Vec2) -> impl Iterator<Item = IVec2> {
+    let (min, max) = (cell(min), cell(max));
+    (min.y..=max.y).flat_map(move |y| (min.x..=max.x).map(move |x| IVec2::new(x, y)))
+}
+
+fn cell(point: Vec2) -> IVec2 {
+    (point / CELL).floor().as_ivec2()
+}
+
+/// Every wall and closed door.
+#[derive(Resource, Debug, Clone, Default)]
+pub struct WallIndex(pub SpatialGrid<Obstacle>);
+
+impl WallIndex {
+    pub fn add(&mut self, obstacle: Obstacle) {
+        let (min, max) = obstacle.bounds();
+        self.0.insert(min, max, obstacle);
+    }
+
+    /// Walls that may be within `radius` of `center`.
+    pub fn near(&self, center: Vec2, radius: f32) -> Vec<&Obstacle> {
+        self.0.near(center, radius)
+    }
+
+    /// Walls that may be in the way between `a` and `b`.
+    pub fn along(&self, a: Vec2, b: Vec2) -> Vec<&Obstacle> {
+        self.0.along(a, b)
+    }
+}
+
+/// Where every character stands, dead or alive.
+#[derive(Resource, Debug, Clone, Default)]
+pub struct CharacterIndex(pub SpatialGrid<(Entity, Vec3)>);
+
+impl CharacterIndex {
+    /// Characters that may be within `radius` of `center`, with where they stand.
+    pub fn near(&self, center: Vec3, radius: f32) -> impl Iterator<Item = (Entity, Vec3)> + '_ {
+        self.0.near(center.xz(), radius).into_iter().copied()
+    }
+}
+
+fn index_walls(walls: Query<(&Collider, &Transform)>, mut index: ResMut<WallIndex>) {
+    index.0.clear();
+    for (collider, transform) in &walls {
+        index.add(Obstacle::from_transform(collider, transform));
+    }
+}
+
+fn index_characters(
+    characters: Query<(Entity, &Transform), Or<(With<Player>, With<Ghost>, With<Npc>)>>,
+    mut index: ResMut<CharacterIndex>,
+) {
+    index.0.clear();
+    for (entity, transform) in &characters {
+        index
+            .0
+            .insert_point(transform.translation.xz(), (entity, transform.translation));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::*;
+
+    fn overlaps((min, max): (Vec2, Vec2), (other_min, other_max): (Vec2, Vec2)) -> bool {
+        min.cmple(other_max).all() && other_min.cmple(max).all()
+    }
+
+    #[test]
+    fn queries_match_going_through_everything() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut rect = |size: f32| {
+            let min = Vec2::new(rng.gen_range(-30.0..30.0), rng.gen_range(-30.0..30.0));
+            (min, min + Vec2::new(rng.gen_range(0.0..size), rng.gen_range(0.0..size)))
+        };
+        let rects: Vec<(Vec2, Vec2)> = (0..300).map(|_| rect(10.0)).collect();
+        let mut grid = SpatialGrid::default();
+        for (index, &(min, max)) in rects.iter().enumerate() {
+            grid.insert(min, max, index);
+        }
+        for _ in 0..300 {
+            let query = rect(15.0);
+            let expected: Vec<usize> =
+                (0..rects.len()).filter(|&index| overlaps(rects[index], query)).collect();
+            let found: Vec<usize> = grid
+                .query(query.0, query.1)
+                .into_iter()
+                .copied()
+                .filter(|&index| overlaps(rects[index], query))
+                .collect();
+            assert_eq!(found, expected, "{query:?}");
+        }
+    }
+
+    #[test]
+    fn things_on_cell_borders_are_found_from_both_sides() {
+        let mut grid = SpatialGrid::default();
+        grid.insert_point(Vec2::new(CELL, 0.5), "on the border");
+        grid.insert(Vec2::new(-1.0, -1.0), Vec2::new(1.0, 1.0), "around the origin");
+        assert!(grid.near(Vec2::new(CELL - 0.1, 0.5), 0.2).contains(&&"on the border"));
+        assert!(grid.near(Vec2::new(CELL + 0.1, 0.5), 0.2).contains(&&"on the border"));
+        for corner in [Vec2::splat(-0.9), Vec2::new(0.9, -0.9), Vec2::new(-0.9, 0.9), Vec2::ONE] {
+            assert_eq!(grid.near(corner, 0.05), vec![&"around the origin"], "{corner}");
+        }
+    }
+
+    #[test]
+    fn negative_coordinates_get_their_own_cells() {
+        let mut grid = SpatialGrid::default();
+        grid.insert_point(Vec2::new(-0.1, -0.1), "just below zero");
+        grid.insert_point(Vec2::new(-CELL * 2.5, -CELL * 2.5), "far below zero");
+        assert_eq!(grid.near(Vec2::new(-0.2, -0.2), 0.2), vec![&"just below zero"]);
+        assert!(grid.near(Vec2::new(0.5, 0.5), 0.1).is_empty());
+        assert_eq!(grid.along(Vec2::ZERO, Vec2::splat(-CELL * 3.0)).len(), 2);
+    }
+
+    #[test]
+    fn results_keep_insertion_order_without_repeats() {
+        let mut grid = SpatialGrid::default();
+        // Each covers several cells, so a query touching all of them sees each one more than once.
+        for index in 0..10 {
+            let offset = Vec2::splat(9.0 - index as f32);
+            grid.insert(-offset, offset, index);
+        }
+        let found: Vec<i32> = grid.near(Vec2::ZERO, 20.0).into_iter().copied().collect();
+        assert_eq!(found, (0..10).collect::<Vec<_>>());
+        grid.clear();
+        assert!(grid.near(Vec2::ZERO, 20.0).is_empty());
+    }
+}
@@ -4,6 +4,7 @@ use bevy::prelude::*;
 
 use super::player::SpawnPlayer;
 use super::stage::SpawnStage;
+use crate::game::{campaign::CurrentLevel, level::Level};
 
 pub(super) fn plugin(app: &mut App) {
     app.observe(spawn_level);
@@ -12,10 +13,20 @@ pub(super) fn plugin(app: &mut App) {
 #[derive(Event, Debug)]
 pub struct SpawnLevel;
 
-fn spawn_level(_trigger: Trigger<SpawnLevel>, mut commands: Commands) {
-    // The only thing we have in our level is a player,
-    // but add things like walls etc. here.
-    commands.trigger(SpawnPlayer);
-    commands.trigger(SpawnStage);
-    
+fn spawn_level(
+    _trigger: Trigger<SpawnLevel>,
+    mut commands: Commands,
+    current_level: Res<CurrentLevel>,
+    levels: Res<Assets<Level>>,
+) {
+    let Some(level) = levels.get(&current_level.handle) else {
+        error!("Tried to spawn a level before it was loaded");
+        return;
+    };
+    commands.trigger(SpawnPlayer {
+        translation: level.player_start,
+    });
+    commands.trigger(SpawnStage {
+        level: current_level.handle.clone_weak(),
+    });
 }
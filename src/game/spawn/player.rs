@@ -5,9 +5,12 @@ use bevy::prelude::*;
 use crate::{
     game::{
         assets::{HandleMap,SceneKey,Action,NlaTrack},
+        collision::CHARACTER_RADIUS,
+        interpolation::Interpolated,
         movement::{Movement, MovementController},
+        world::Carrying,
     },
-    screen::Screen,
+    screen::InGame,
 };
 
 pub(super) fn plugin(app: &mut App) {
@@ -16,12 +19,19 @@ pub(super) fn plugin(app: &mut App) {
 }
 
 #[derive(Event, Debug)]
-pub struct SpawnPlayer;
+pub struct SpawnPlayer {
+    pub translation: Vec3,
+}
 
 #[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Default, Reflect)]
 #[reflect(Component)]
 pub struct Player;
 
+/// The lamp that hangs over the player, which guards see them by.
+pub const LAMP_HEIGHT: f32 = 6.0;
+pub const LAMP_INTENSITY: f32 = 10_000_000.0;
+pub const LAMP_RANGE: f32 = 50.0;
+
 //#[derive(Component, Debug, Clone, Copy, PartialEq, Default, Reflect)]
 //#[reflect(Component)]
 //pub struct CameraPosition{
@@ -29,7 +39,7 @@ pub struct Player;
 //}
 
 fn spawn_player(
-    _trigger: Trigger<SpawnPlayer>,
+    trigger: Trigger<SpawnPlayer>,
     mut commands: Commands,
  //   camera:Query<Entity,With<Camera3d>>,
     scene_handles: Res<HandleMap<SceneKey>>,
@@ -41,38 +51,40 @@ fn spawn_player(
 
 
 
+    let transform = Transform::from_translation(trigger.event().translation);
     commands.spawn((
         Name::new("Player"),
         SceneBundle{
             scene:scene_handles[&SceneKey::Character].clone_weak(),
-            transform:Transform::from_translation(Vec3::new(-0.0,0.0,-0.0)),
+            transform,
             ..Default::default()
         },
+        Interpolated::new(transform),
         MovementController::default(),
-        Movement { speed: 5.5, rotation:3.0 },
+        Carrying::default(),
+        Movement { speed: 5.5, rotation:3.0, radius:CHARACTER_RADIUS },
         Action{
             current_track:NlaTrack::Idle,
             new_track:NlaTrack::Idle,
         },
-        StateScoped(Screen::Playing),
+        StateScoped(InGame),
         Player,
-    )).with_children( |child_builder|   {
-        child_builder.spawn((Name::new("Light"),
-            PointLightBundle{
-                point_light:PointLight{
-                    color:Color::srgb(1.0,1.0,1.0),
-                    intensity:10000000.0,
-                    shadows_enabled:true,
-                    range:50.0,
+    )).with_children(|child_builder| {
+        child_builder.spawn((
+            Name::new("Light"),
+            PointLightBundle {
+                point_light: PointLight {
+                    color: Color::srgb(1.0, 1.0, 1.0),
+                    intensity: LAMP_INTENSITY,
+                    shadows_enabled: true,
+                    range: LAMP_RANGE,
                     ..Default::default()
                 },
-                transform:Transform::from_translation(Vec3::new(0.0,6.0,0.0)),
+                transform: Transform::from_translation(Vec3::Y * LAMP_HEIGHT),
                 ..Default::default()
-
-
-        },
-        StateScoped(Screen::Playing),
-    ));
+            },
+            StateScoped(InGame),
+        ));
     });
 
     //commands.entity(camera.single()).insert(CameraPosition{player:Vec3::new(-5.7, 20.7,-20.0)});
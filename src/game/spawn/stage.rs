@@ -1,7 +1,11 @@
-use std::time::Duration;
+use std::{f32::consts::FRAC_PI_2, time::Duration};
 
 use bevy::{
     prelude::*,
+    render::{
+        mesh::{Indices, PrimitiveTopology},
+        render_asset::RenderAssetUsages,
+    },
 //    core_pipeline::Skybox,
 };
 
@@ -10,9 +14,17 @@ use crate::{
         assets::{Action, Animations, GraphKey, HandleMap, MaterialKey, MeshKey, SceneKey,NlaTrack
 //            ImageKey
         },
-        movement::{GhostPath, Npc, Path, Timeloop},
+        collision::edges,
+        ghosts::GhostLimit,
+        guards::{camera_pose, Alarm, GuardAi, Route},
+        level::{DoorData, FurnaceData, GuardData, ItemData, Level, SwitchData, WallData, WallShape},
+        interpolation::Interpolated,
+        navigation::NavGrid,
+        movement::{sample_path, Npc, Path, Timeloop},
+        recording::GhostRecording,
+        world::{Door, Item, Switch, WorldObject},
     },
-    screen::Screen,
+    screen::InGame,
     
 };
 
@@ -22,33 +34,37 @@ pub(super) fn plugin(app: &mut App) {
     app.register_type::<Wall>();
     app.register_type::<Furnace>();
 
-    app.add_systems(Update,setup_scene_once_loaded.run_if(in_state(Screen::Playing)));
-    app.init_resource::<GhostPath>();
+    app.add_systems(Update,setup_scene_once_loaded.run_if(in_state(InGame)));
     app.init_resource::<Timeloop>();
 }
 
 #[derive(Event, Debug)]
-pub struct SpawnStage;
+pub struct SpawnStage {
+    pub level: Handle<Level>,
+}
 
 fn spawn_stage(
-    _trigger: Trigger<SpawnStage>,
+    trigger: Trigger<SpawnStage>,
     mut commands: Commands,
     mesh_handles: Res<HandleMap<MeshKey>>,
     material_handles: Res<HandleMap<MaterialKey>>,
     scene_handles: Res<HandleMap<SceneKey>>,
+    levels: Res<Assets<Level>>,
+    mut meshes: ResMut<Assets<Mesh>>,
 //    image_handles:Res<HandleMap<ImageKey>>,
 //    camera:Query<Entity,With<Camera>>,
 ) {
-    // A texture atlas is a way to split one image with a grid into multiple sprites.
-    // By attaching it to a [`SpriteBundle`] and providing an index, we can specify which section of the image we want to see.
-    // We will use this to animate our player character. You can learn more about texture atlases in this example:
-    // https://github.com/bevyengine/bevy/blob/latest/examples/2d/texture_atlas.rs
 //    commands.entity(camera.single()).
 //        insert(Skybox{
 //            image:image_handles[&ImageKey::Black].clone_weak(),
 //            brightness:1.0,
 //        }
 //    );
+    let Some(level) = levels.get(&trigger.event().level) else {
+        error!("Tried to spawn a stage before its level was loaded");
+        return;
+    };
+
     commands.spawn((
         Name::new("Floor"),
         MaterialMeshBundle{
@@ -56,525 +72,283 @@ fn spawn_stage(
             material:material_handles[&MaterialKey::Red].clone_weak(),
             ..Default::default()
         },
-        StateScoped(Screen::Playing),
+        StateScoped(InGame),
     ));
 
-//GUARDS
-    commands.spawn((
-        Name::new("Enemy"),
-        Action{
-            current_track:NlaTrack::Idle,
-            new_track:NlaTrack::Walk,
-        },
-        SceneBundle{
-            scene:scene_handles[&SceneKey::Character].clone_weak(),
-            ..Default::default()
-        },
-        Npc,
-        Path{
-            points: vec![
-                (2.0,Vec3::new(14.0,0.0,-5.0)),
-                (7.0,Vec3::new(14.0,0.0,-15.0)),
-                (17.0,Vec3::new(14.0,0.0,5.0)),
-                (22.0,Vec3::new(14.0,0.0,-5.0)),
-                (27.0,Vec3::new(3.0,0.0,-5.0)),
-                ]
-        },
-        StateScoped(Screen::Playing),
-    ));
-    commands.spawn((
-        Name::new("Enemy2"),
-        Action{
-            current_track:NlaTrack::Idle,
-            new_track:NlaTrack::Walk,
-        },
-        SceneBundle{
-            scene:scene_handles[&SceneKey::Character].clone_weak(),
-            ..Default::default()
-        },
-        Npc,
-        Path{
-            points:vec![
-                (3.0,Vec3::new(3.0,0.0,11.0)),
-                (7.0,Vec3::new(3.0,0.0,6.0)),
-                (18.0,Vec3::new(21.0,0.0,6.0)),
-                (25.0,Vec3::new(21.0,0.0,11.0)),
-            ]
-        },
-        StateScoped(Screen::Playing),
+    let grid = NavGrid::for_level(level);
+    for guard in &level.guards{
+        spawn_guard(&mut commands, &scene_handles, &mesh_handles, &material_handles, &grid, guard, level.max_time);
+    }
+    commands.insert_resource(grid);
 
-    ));
-    commands.spawn((
-        Name::new("Enemy3"),
-        Action{
-            current_track:NlaTrack::Idle,
-            new_track:NlaTrack::Walk,
-        },
-        SceneBundle{
-            scene:scene_handles[&SceneKey::Character].clone_weak(),
-            ..Default::default()
-        },
-        Npc,
-        Path{
-            points:vec![
-                (10.0,Vec3::new(38.0,0.0,-5.0)),
-                (25.0,Vec3::new(24.0,0.0,-5.0)),
-            ]
-        },
-        StateScoped(Screen::Playing),
+    commands.insert_resource(Timeloop::new(level.max_time));
+    commands.insert_resource(level.paradox);
+    commands.insert_resource(GhostLimit(level.max_ghosts));
+    commands.insert_resource(Alarm::new(level.alarm_bodies));
+    commands.insert_resource(GhostRecording::new(Transform::from_translation(level.player_start)));
 
-    ));
-    commands.spawn((
-        Name::new("Enemy4"),
-        Action{
-            current_track:NlaTrack::Idle,
-            new_track:NlaTrack::Walk,
-        },
-        SceneBundle{
-            scene:scene_handles[&SceneKey::Character].clone_weak(),
-            ..Default::default()
-        },
-        Npc,
-        Path{
-            points:vec![
-                (10.0,Vec3::new(24.0,0.0,-7.0)),
-                (25.0,Vec3::new(38.0,0.0,-7.0)),
-            ]
-        },
-        StateScoped(Screen::Playing),
+    for furnace in &level.furnaces{
+        spawn_furnace(&mut commands, furnace);
+    }
 
-    ));
-    commands.spawn((
-        Name::new("Enemy4"),
+    for wall in &level.walls{
+        spawn_wall(&mut commands, &mesh_handles, &material_handles, &mut meshes, wall);
+    }
+
+    for door in &level.doors{
+        spawn_door(&mut commands, &mesh_handles, &material_handles, door);
+    }
+
+    for switch in &level.switches{
+        spawn_switch(&mut commands, &mesh_handles, &material_handles, switch);
+    }
+
+    for item in &level.items{
+        spawn_item(&mut commands, &mesh_handles, &material_handles, item);
+    }
+}
+
+fn spawn_guard(
+    commands: &mut Commands,
+    scene_handles: &HandleMap<SceneKey>,
+    mesh_handles: &HandleMap<MeshKey>,
+    material_handles: &HandleMap<MaterialKey>,
+    grid: &NavGrid,
+    guard: &GuardData,
+    max_time: f32,
+){
+    let profile = guard.kind.profile();
+    // Guards that never leave their post only look at their waypoints.
+    let walks = profile.speed > 0.0;
+    let points = if walks {
+        grid.route_path(&guard.path, max_time)
+    } else {
+        guard.path.clone()
+    };
+    let transform = if walks {
+        sample_path(&points, 0.0, max_time)
+            .map_or(Transform::IDENTITY, |(translation, direction)| {
+                Transform::from_translation(translation).looking_to(direction, Vec3::Y)
+            })
+    } else {
+        camera_pose(&points, 0.0, max_time)
+    }.with_scale(Vec3::splat(profile.scale));
+    let mut entity = commands.spawn((
+        Name::new(guard.name.clone()),
         Action{
             current_track:NlaTrack::Idle,
             new_track:NlaTrack::Walk,
         },
-        SceneBundle{
-            scene:scene_handles[&SceneKey::Character].clone_weak(),
-            ..Default::default()
-        },
+        Interpolated::new(transform),
         Npc,
+        guard.kind,
+        GuardAi::default(),
+        Route::default(),
         Path{
-            points:vec![
-                (7.0,Vec3::new(12.0,0.0,-26.0)),
-                (10.0,Vec3::new(12.0,0.0,-20.0)),
-                (13.0,Vec3::new(12.0,0.0,-26.0)),
-                (20.0,Vec3::new(-2.0,0.0,-26.0)),
-                (24.0,Vec3::new(-2.0,0.0,-20.0)),
-                (28.0,Vec3::new(-2.0,0.0,-26.0)),
-            ]
+            points,
         },
-        StateScoped(Screen::Playing),
-
+        StateScoped(InGame),
     ));
-    commands.spawn((
-        Name::new("Enemy4"),
-        Action{
-            current_track:NlaTrack::Idle,
-            new_track:NlaTrack::Walk,
-        },
-        SceneBundle{
+    if walks {
+        entity.insert(SceneBundle{
             scene:scene_handles[&SceneKey::Character].clone_weak(),
+            transform,
             ..Default::default()
-        },
-        Npc,
-        Path{
-            points:vec![
-                (4.0,Vec3::new(33.0,0.0,-15.0)),
-                (8.0,Vec3::new(21.0,0.0,-22.0)),
-                (12.0,Vec3::new(24.0,0.0,-24.0)),
-                (16.0,Vec3::new(24.0,0.0,-30.0)),
-                (20.0,Vec3::new(20.0,0.0,-30.0)),
-                (24.0,Vec3::new(34.0,0.0,-30.0)),
-                (29.0,Vec3::new(37.0,0.0,-23.0)),
-                (2.0,Vec3::new(28.0,0.0,-18.0)),
-
-            ]
-        },
-        StateScoped(Screen::Playing),
-
-    ));
+        });
+    } else {
+        entity.insert(SpatialBundle::from_transform(transform)).with_children(|children| {
+            children.spawn((
+                Name::new("Lens"),
+                MaterialMeshBundle{
+                    mesh:mesh_handles[&MeshKey::Capsule].clone_weak(),
+                    material:material_handles[&MaterialKey::Blue].clone_weak(),
+                    // Mounted up high, pointing the way it looks.
+                    transform:Transform::from_xyz(0.0, 2.5, 0.0)
+                        .with_rotation(Quat::from_rotation_x(FRAC_PI_2))
+                        .with_scale(Vec3::new(0.3, 0.4, 0.3)),
+                    ..Default::default()
+                },
+            ));
+        });
+    }
+}
 
-    
-    commands.insert_resource(Timeloop{
-        current_time:0.0,
-        max_time:30.0,
-        gen:0,
-    });
-    commands.insert_resource(GhostPath{points:vec![(0.0,Vec3::ZERO)]});
-
-//FURNACES
-    commands.spawn((
-        Name::new("Furnace0"),
-        Furnace{
-            countdown:0.0
-        },
-        PointLightBundle{
-            
-            point_light:PointLight{
-                color:Color::srgb(1.0,0.0,0.0),
-                intensity:100000000000.0,
-                shadows_enabled:true,
-                range:500.0,
-                ..Default::default()
-            },
-            transform:Transform::from_translation(
-                Vec3::new(12.0,1.0,-23.0)),
-            ..Default::default()
-        },
-        StateScoped(Screen::Playing),
-    ));
-    commands.spawn((
-        Name::new("Furnace1"),
-        Furnace{
-            countdown:0.0
-        },
-        PointLightBundle{
-            
-            point_light:PointLight{
-                color:Color::srgb(1.0,0.0,0.0),
-                intensity:100000000000.0,
-                shadows_enabled:true,
-                range:500.0,
-                ..Default::default()
-            },
-            transform:Transform::from_translation(
-                Vec3::new(21.0,1.0,6.0)),
-            ..Default::default()
-        },
-        StateScoped(Screen::Playing),
-    ));
+fn spawn_furnace(
+    commands: &mut Commands,
+    furnace: &FurnaceData,
+){
     commands.spawn((
-        Name::new("Furnace2"),
+        Name::new(furnace.name.clone()),
         Furnace{
             countdown:0.0
         },
         PointLightBundle{
-            
             point_light:PointLight{
                 color:Color::srgb(1.0,0.0,0.0),
                 intensity:100000000000.0,
                 shadows_enabled:true,
-                range:500.0,
+                range:FURNACE_RANGE,
                 ..Default::default()
             },
-            transform:Transform::from_translation(
-                Vec3::new(3.0,1.0,11.0)),
+            transform:Transform::from_translation(furnace.translation),
             ..Default::default()
         },
-        StateScoped(Screen::Playing),
-    ));
-
-//WALLS    
-    commands.spawn((
-        Name::new("Wall"),
-
-        Wall,
-        MaterialMeshBundle{
-            transform:Transform{
-                translation:(Vec3::new(3.0,2.0,-13.0)),
-                scale:(Vec3::new(9.0,5.0,1.0)),
-                ..default()
-            },
-            mesh: mesh_handles[&MeshKey::Wall].clone_weak(),
-            material:material_handles[&MaterialKey::Blue].clone_weak(),
-            ..default()
-        },
-        StateScoped(Screen::Playing),
-    ));
-    commands.spawn((
-        Name::new("Wall"),
-
-        Wall,
-        MaterialMeshBundle{
-            transform:Transform{
-                translation:(Vec3::new(3.0,2.0,3.0)),
-                scale:(Vec3::new(9.0,5.0,1.0)),
-                ..default()
-            },
-            mesh: mesh_handles[&MeshKey::Wall].clone_weak(),
-            material:material_handles[&MaterialKey::Blue].clone_weak(),
-            ..default()
-        },
-        StateScoped(Screen::Playing),
+        StateScoped(InGame),
     ));
+}
 
+fn spawn_wall(
+    commands: &mut Commands,
+    mesh_handles: &HandleMap<MeshKey>,
+    material_handles: &HandleMap<MaterialKey>,
+    meshes: &mut Assets<Mesh>,
+    wall: &WallData,
+){
+    let (mesh, scale) = match &wall.shape {
+        WallShape::Box => (mesh_handles[&MeshKey::Wall].clone_weak(), wall.scale),
+        WallShape::Pillar => (
+            mesh_handles[&MeshKey::Pillar].clone_weak(),
+            Vec3::new(wall.scale.x, wall.scale.y, wall.scale.x),
+        ),
+        WallShape::Polygon(points) => (meshes.add(prism_mesh(points, wall.scale.y)), Vec3::ONE),
+    };
     commands.spawn((
         Name::new("Wall"),
-
         Wall,
+        wall.collider(),
         MaterialMeshBundle{
             transform:Transform{
-                translation:(Vec3::new(11.0,2.0,-1.0)),
-                scale:(Vec3::new(1.0,5.0,3.0)),
-                ..default()
+                translation:wall.translation,
+                rotation:Quat::from_rotation_y(wall.rotation),
+                scale,
             },
-            mesh: mesh_handles[&MeshKey::Wall].clone_weak(),
+            mesh,
             material:material_handles[&MaterialKey::Blue].clone_weak(),
             ..default()
         },
-        StateScoped(Screen::Playing),
-
+        StateScoped(InGame),
     ));
-    commands.spawn((
-        Name::new("Wall"),
-
-        Wall,
-        MaterialMeshBundle{
-            transform:Transform{
-                translation:(Vec3::new(11.0,2.0,-9.0)),
-                scale:(Vec3::new(1.0,5.0,3.0)),
-                ..default()
-            },
-            mesh: mesh_handles[&MeshKey::Wall].clone_weak(),
-            material:material_handles[&MaterialKey::Blue].clone_weak(),
-            ..default()
-        },
-        StateScoped(Screen::Playing),
+}
 
-    ));
-    commands.spawn((
-        Name::new("Wall"),
-        Wall,
-        MaterialMeshBundle{
-            transform:Transform{
-                translation:(Vec3::new(-5.0,2.0,-5.0)),
-                scale:(Vec3::new(1.0,5.0,7.0)),
-                ..default()
-            },
-            mesh: mesh_handles[&MeshKey::Wall].clone_weak(),
-            material:material_handles[&MaterialKey::Blue].clone_weak(),
-            ..default()
-        },
-        StateScoped(Screen::Playing),
-    ));
-    commands.spawn((
-        Name::new("Wall"),
-        Wall,
-        MaterialMeshBundle{
-            transform:Transform{
-                translation:(Vec3::new(17.0,2.0,-5.0)),
-                scale:(Vec3::new(1.0,5.0,7.0)),
-                ..default()
-            },
-            mesh: mesh_handles[&MeshKey::Wall].clone_weak(),
-            material:material_handles[&MaterialKey::Blue].clone_weak(),
-            ..default()
-        },
-        StateScoped(Screen::Playing),
-    ));
-    commands.spawn((
-        Name::new("Wall"),
-        Wall,
-        MaterialMeshBundle{
-            transform:Transform{
-                translation:(Vec3::new(14.0,2.0,-18.0)),
-                scale:(Vec3::new(9.0,5.0,1.0)),
-                ..default()
-            },
-            mesh: mesh_handles[&MeshKey::Wall].clone_weak(),
-            material:material_handles[&MaterialKey::Blue].clone_weak(),
-            ..default()
-        },
-        StateScoped(Screen::Playing),
-    ));
-    commands.spawn((
-        Name::new("Wall"),
-        Wall,
-        MaterialMeshBundle{
-            transform:Transform{
-                translation:(Vec3::new(14.0,2.0,-28.0)),
-                scale:(Vec3::new(9.0,5.0,1.0)),
-                ..default()
-            },
-            mesh: mesh_handles[&MeshKey::Wall].clone_weak(),
-            material:material_handles[&MaterialKey::Blue].clone_weak(),
-            ..default()
-        },
-        StateScoped(Screen::Playing),
-    ));
-    commands.spawn((
-        Name::new("Wall"),
-        Wall,
-        MaterialMeshBundle{
-            transform:Transform{
-                translation:(Vec3::new(18.0,2.0,-21.0)),
-                scale:(Vec3::new(1.0,5.0,7.0)),
-                ..default()
-            },
-            mesh: mesh_handles[&MeshKey::Wall].clone_weak(),
-            material:material_handles[&MaterialKey::Blue].clone_weak(),
-            ..default()
-        },
-        StateScoped(Screen::Playing),
-    ));
-    commands.spawn((
-        Name::new("Wall"),
-        Wall,
-        MaterialMeshBundle{
-            transform:Transform{
-                translation:(Vec3::new(6.0,2.0,-22.0)),
-                scale:(Vec3::new(1.0,5.0,3.0)),
-                ..default()
-            },
-            mesh: mesh_handles[&MeshKey::Wall].clone_weak(),
-            material:material_handles[&MaterialKey::Blue].clone_weak(),
-            ..default()
-        },
-        StateScoped(Screen::Playing),
-    ));
-    commands.spawn((
-        Name::new("Wall"),
-        Wall,
-        MaterialMeshBundle{
-            transform:Transform{
-                translation:(Vec3::new(13.0,2.0,14.0)),
-                scale:(Vec3::new(12.0,5.0,1.0)),
-                ..default()
-            },
-            mesh: mesh_handles[&MeshKey::Wall].clone_weak(),
-            material:material_handles[&MaterialKey::Blue].clone_weak(),
-            ..default()
-        },
-        StateScoped(Screen::Playing),
-    ));
-    commands.spawn((
-        Name::new("Wall"),
-        Wall,
-        MaterialMeshBundle{
-            transform:Transform{
-                translation:(Vec3::new(24.0,2.0,6.0)),
-                scale:(Vec3::new(1.0,5.0,7.0)),
-                ..default()
-            },
-            mesh: mesh_handles[&MeshKey::Wall].clone_weak(),
-            material:material_handles[&MaterialKey::Blue].clone_weak(),
-            ..default()
-        },
-        StateScoped(Screen::Playing),
-    ));
+fn spawn_door(
+    commands: &mut Commands,
+    mesh_handles: &HandleMap<MeshKey>,
+    material_handles: &HandleMap<MaterialKey>,
+    door: &DoorData,
+){
     commands.spawn((
-        Name::new("Wall"),
-        Wall,
-        MaterialMeshBundle{
-            transform:Transform{
-                translation:(Vec3::new(0.0,2.0,9.0)),
-                scale:(Vec3::new(1.0,5.0,7.0)),
-                ..default()
-            },
-            mesh: mesh_handles[&MeshKey::Wall].clone_weak(),
-            material:material_handles[&MaterialKey::Blue].clone_weak(),
-            ..default()
+        Name::new(door.name.clone()),
+        WorldObject{
+            persistence:door.persistence,
+            used:false,
         },
-        StateScoped(Screen::Playing),
-    ));
-    commands.spawn((
-        Name::new("Wall"),
-        Wall,
-        MaterialMeshBundle{
-            transform:Transform{
-                translation:(Vec3::new(3.0,2.0,-13.0)),
-                scale:(Vec3::new(9.0,5.0,1.0)),
-                ..default()
-            },
-            mesh: mesh_handles[&MeshKey::Wall].clone_weak(),
-            material:material_handles[&MaterialKey::Blue].clone_weak(),
-            ..default()
+        Door{
+            key:door.key.clone(),
+            collider:door.collider(),
         },
-        StateScoped(Screen::Playing),
-    ));
-    commands.spawn((
-        Name::new("Wall"),
-        Wall,
+        door.collider(),
         MaterialMeshBundle{
             transform:Transform{
-                translation:(Vec3::new(31.0,2.0,-2.0)),
-                scale:(Vec3::new(9.0,5.0,1.0)),
-                ..default()
+                translation:door.translation,
+                rotation:Quat::from_rotation_y(door.rotation),
+                scale:door.scale,
             },
-            mesh: mesh_handles[&MeshKey::Wall].clone_weak(),
-            material:material_handles[&MaterialKey::Blue].clone_weak(),
+            mesh:mesh_handles[&MeshKey::Wall].clone_weak(),
+            material:material_handles[&MaterialKey::Green].clone_weak(),
             ..default()
         },
-        StateScoped(Screen::Playing),
+        StateScoped(InGame),
     ));
+}
+
+fn spawn_switch(
+    commands: &mut Commands,
+    mesh_handles: &HandleMap<MeshKey>,
+    material_handles: &HandleMap<MaterialKey>,
+    switch: &SwitchData,
+){
     commands.spawn((
-        Name::new("Wall"),
-        Wall,
-        MaterialMeshBundle{
-            transform:Transform{
-                translation:(Vec3::new(29.0,2.0,-10.0)),
-                scale:(Vec3::new(7.0,5.0,1.0)),
-                ..default()
-            },
-            mesh: mesh_handles[&MeshKey::Wall].clone_weak(),
-            material:material_handles[&MaterialKey::Blue].clone_weak(),
-            ..default()
+        Name::new(switch.name.clone()),
+        WorldObject{
+            persistence:switch.persistence,
+            used:false,
         },
-        StateScoped(Screen::Playing),
-    ));
-    commands.spawn((
-        Name::new("Wall"),
-        Wall,
-        MaterialMeshBundle{
-            transform:Transform{
-                translation:(Vec3::new(-7.0,2.0,-21.0)),
-                scale:(Vec3::new(1.0,5.0,11.0)),
-                ..default()
-            },
-            mesh: mesh_handles[&MeshKey::Wall].clone_weak(),
-            material:material_handles[&MaterialKey::Blue].clone_weak(),
-            ..default()
+        Switch{
+            doors:switch.doors.clone(),
         },
-        StateScoped(Screen::Playing),
-    ));
-    commands.spawn((
-        Name::new("Wall"),
-        Wall,
         MaterialMeshBundle{
             transform:Transform{
-                translation:(Vec3::new(11.0,2.0,-33.0)),
-                scale:(Vec3::new(30.0,5.0,1.0)),
+                translation:switch.translation.with_y(0.05),
+                scale:Vec3::new(0.6, 0.05, 0.6),
                 ..default()
             },
-            mesh: mesh_handles[&MeshKey::Wall].clone_weak(),
-            material:material_handles[&MaterialKey::Blue].clone_weak(),
+            mesh:mesh_handles[&MeshKey::Pillar].clone_weak(),
+            material:material_handles[&MaterialKey::Red].clone_weak(),
             ..default()
         },
-        StateScoped(Screen::Playing),
+        StateScoped(InGame),
     ));
+}
+
+fn spawn_item(
+    commands: &mut Commands,
+    mesh_handles: &HandleMap<MeshKey>,
+    material_handles: &HandleMap<MaterialKey>,
+    item: &ItemData,
+){
     commands.spawn((
-        Name::new("Wall"),
-        Wall,
-        MaterialMeshBundle{
-            transform:Transform{
-                translation:(Vec3::new(40.0,2.0,-21.0)),
-                scale:(Vec3::new(1.0,5.0,19.0)),
-                ..default()
-            },
-            mesh: mesh_handles[&MeshKey::Wall].clone_weak(),
-            material:material_handles[&MaterialKey::Blue].clone_weak(),
-            ..default()
+        Name::new(item.name.clone()),
+        WorldObject{
+            persistence:item.persistence,
+            used:false,
         },
-        StateScoped(Screen::Playing),
-    ));
-    commands.spawn((
-        Name::new("Wall"),
-        Wall,
+        Item,
         MaterialMeshBundle{
-            transform:Transform{
-                translation:(Vec3::new(27.0,2.0,-25.0)),
-                scale:(Vec3::new(1.0,5.0,3.0)),
-                ..default()
-            },
-            mesh: mesh_handles[&MeshKey::Wall].clone_weak(),
-            material:material_handles[&MaterialKey::Blue].clone_weak(),
+            transform:Transform::from_translation(item.translation).with_scale(Vec3::splat(0.3)),
+            mesh:mesh_handles[&MeshKey::Capsule].clone_weak(),
+            material:material_handles[&MaterialKey::Green].clone_weak(),
             ..default()
         },
-        StateScoped(Screen::Playing),
+        StateScoped(InGame),
     ));
+}
+
+/// A straight wall with the given outline, reaching `half_height` above and below its origin.
+fn prism_mesh(points: &[Vec2], half_height: f32) -> Mesh {
+    // Front faces wind counter clockwise seen from outside, which for the top
+    // face means a clockwise outline in (x, z).
+    let mut outline = points.to_vec();
+    let area: f32 = edges(&outline).map(|(a, b)| a.perp_dot(b)).sum();
+    if area > 0.0 {
+        outline.reverse();
+    }
 
+    let mut positions: Vec<[f32; 3]> = vec![];
+    let mut normals: Vec<[f32; 3]> = vec![];
+    let mut indices: Vec<u32> = vec![];
+    for (a, b) in edges(&outline) {
+        let normal = Vec3::new(a.y - b.y, 0.0, b.x - a.x).normalize_or_zero();
+        let base = positions.len() as u32;
+        positions.extend([
+            [a.x, -half_height, a.y],
+            [b.x, -half_height, b.y],
+            [b.x, half_height, b.y],
+            [a.x, half_height, a.y],
+        ]);
+        normals.extend([normal.to_array(); 4]);
+        indices.extend([base, base + 1, base + 2, base, base + 2, base + 3]);
+    }
+    let base = positions.len() as u32;
+    positions.extend(outline.iter().map(|point| [point.x, half_height, point.y]));
+    normals.extend(outline.iter().map(|_| [0.0, 1.0, 0.0]));
+    for index in 1..outline.len().saturating_sub(1) as u32 {
+        indices.extend([base, base + index, base + index + 1]);
+    }
+
+    Mesh::new(PrimitiveTopology::TriangleList, RenderAssetUsages::default())
+        .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, positions)
+        .with_inserted_attribute(Mesh::ATTRIBUTE_NORMAL, normals)
+        .with_inserted_indices(Indices::U32(indices))
 }
 
 #[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Default, Reflect)]
@@ -587,6 +361,16 @@ pub struct Furnace{
     pub countdown:f32,
 }
 
+/// How far a furnace's light reaches.
+pub const FURNACE_RANGE: f32 = 500.0;
+
+impl Furnace{
+    /// Light given off, in lumens. The longer nobody tends it the hotter it burns.
+    pub fn intensity(&self)->f32{
+        f32::powf(1.8, self.countdown)
+    }
+}
+
 fn setup_scene_once_loaded(
     mut commands: Commands,
     animations: Res<Animations>,
@@ -612,14 +396,9 @@ fn setup_scene_once_loaded(
     }
 }
 
-impl FromWorld for GhostPath {
-    fn from_world(_: &mut World) -> Self {
-        GhostPath{points:vec![(0.0,Vec3::ZERO)]}
-    }
-}
 impl FromWorld for Timeloop {
     fn from_world(_: &mut World) -> Self {
-        Timeloop { current_time: 0.0, max_time: 0.0, r#gen: 0 }
+        Timeloop { tick: 0, max_ticks: 1, r#gen: 0 }
     }
 
 }
\ No newline at end of file
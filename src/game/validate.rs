@@ -0,0 +1,482 @@
+//! Checks a [`Level`] for mistakes that make it broken or unfair.
+//! This needs nothing but the level data, so it also runs headless in the
+//! `lint_level` binary.
+
+use std::fmt;
+
+use bevy::prelude::*;
+
+use super::{
+    collision::{is_convex, Obstacle, CHARACTER_RADIUS},
+    level::{Level, WallShape},
+    movement::KILL_RANGE,
+    navigation::NavGrid,
+    world::REACH,
+};
+
+/// How close a character needs to get to a furnace to keep it burning.
+const FURNACE_REACH: f32 = 3.0;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LevelIssue {
+    EmptyPath { guard: String },
+    WaypointOutOfLoop { guard: String, index: usize, time: f32, max_time: f32 },
+    WaypointOutOfOrder { guard: String, index: usize, time: f32, previous: f32 },
+    NoRoute { guard: String, from: usize, to: usize },
+    PlayerInsideWall { wall: usize },
+    UnreachableFurnace { furnace: String },
+    UnreachableGuard { guard: String },
+    NothingToKill,
+    DuplicateWall { wall: usize, original: usize },
+    InvalidWallShape { wall: usize },
+    DuplicateName { name: String },
+    UnknownDoor { switch: String, door: String },
+    UnknownKey { door: String, item: String },
+}
+
+impl fmt::Display for LevelIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LevelIssue::EmptyPath { guard } => write!(f, "guard {guard:?} has no waypoints"),
+            LevelIssue::WaypointOutOfLoop { guard, index, time, max_time } => write!(
+                f,
+                "guard {guard:?} waypoint {index} has time {time}, outside of the loop (0 to {max_time})"
+            ),
+            LevelIssue::WaypointOutOfOrder { guard, index, time, previous } => write!(
+                f,
+                "guard {guard:?} waypoint {index} has time {time}, but the one before it has {previous}"
+            ),
+            LevelIssue::NoRoute { guard, from, to } => write!(
+                f,
+                "guard {guard:?} can't get from waypoint {from} to waypoint {to} around the walls"
+            ),
+            LevelIssue::PlayerInsideWall { wall } => write!(f, "player starts inside wall {wall}"),
+            LevelIssue::UnreachableFurnace { furnace } => {
+                write!(f, "furnace {furnace:?} can't be reached from the player's start")
+            }
+            LevelIssue::UnreachableGuard { guard } => write!(
+                f,
+                "guard {guard:?} can't be reached from the player's start, so the level can't be won"
+            ),
+            LevelIssue::NothingToKill => {
+                write!(f, "no guard can be killed, so the level is won as soon as it starts")
+            }
+            LevelIssue::DuplicateWall { wall, original } => {
+                write!(f, "wall {wall} is a copy of wall {original}")
+            }
+            LevelIssue::InvalidWallShape { wall } => {
+                write!(f, "wall {wall} is not a convex polygon with at least three corners")
+            }
+            LevelIssue::DuplicateName { name } => write!(f, "more than one thing is named {name:?}"),
+            LevelIssue::UnknownDoor { switch, door } => {
+                write!(f, "switch {switch:?} opens door {door:?}, which doesn't exist")
+            }
+            LevelIssue::UnknownKey { door, item } => {
+                write!(f, "door {door:?} is opened by item {item:?}, which doesn't exist")
+            }
+        }
+    }
+}
+
+pub fn validate_level(level: &Level) -> Vec<LevelIssue> {
+    let mut issues = vec![];
+    let grid = NavGrid::for_level(level);
+    check_paths(level, &grid, &mut issues);
+    check_walls(level, &mut issues);
+    check_reachability(level, &grid, &mut issues);
+    check_names(level, &mut issues);
+    check_win(level, &mut issues);
+    check_world_objects(level, &mut issues);
+    issues
+}
+
+fn check_paths(level: &Level, grid: &NavGrid, issues: &mut Vec<LevelIssue>) {
+    for guard in &level.guards {
+        let path = &guard.path;
+        if path.is_empty() {
+            issues.push(LevelIssue::EmptyPath { guard: guard.name.clone() });
+            continue;
+        }
+        for (index, &(time, _)) in path.iter().enumerate() {
+            if !(0.0..=level.max_time).contains(&time) {
+                issues.push(LevelIssue::WaypointOutOfLoop {
+                    guard: guard.name.clone(),
+                    index,
+                    time,
+                    max_time: level.max_time,
+                });
+            }
+            if let Some(&(previous, _)) = index.checked_sub(1).map(|i| &path[i]) {
+                if time <= previous {
+                    issues.push(LevelIssue::WaypointOutOfOrder {
+                        guard: guard.name.clone(),
+                        index,
+                        time,
+                        previous,
+                    });
+                }
+            }
+        }
+        // Guards that never walk only look at the rest of their waypoints.
+        if guard.kind.profile().speed <= 0.0 {
+            continue;
+        }
+        // Guards walk from the last waypoint back to the first one as the loop wraps.
+        for from in 0..path.len() {
+            let to = (from + 1) % path.len();
+            if grid.find_path(path[from].1.xz(), path[to].1.xz()).is_empty() {
+                issues.push(LevelIssue::NoRoute { guard: guard.name.clone(), from, to });
+            }
+        }
+    }
+}
+
+fn check_walls(level: &Level, issues: &mut Vec<LevelIssue>) {
+    for (index, wall) in level.walls.iter().enumerate() {
+        if let Some(original) = level.walls[..index].iter().position(|other| other == wall) {
+            issues.push(LevelIssue::DuplicateWall { wall: index, original });
+        }
+        if let WallShape::Polygon(points) = &wall.shape {
+            if !is_convex(points) {
+                issues.push(LevelIssue::InvalidWallShape { wall: index });
+                continue;
+            }
+        }
+        if wall
+            .obstacle()
+            .penetration(level.player_start.xz(), CHARACTER_RADIUS)
+            .is_some()
+        {
+            issues.push(LevelIssue::PlayerInsideWall { wall: index });
+        }
+    }
+}
+
+fn check_names(level: &Level, issues: &mut Vec<LevelIssue>) {
+    let names: Vec<&String> = level
+        .guards
+        .iter()
+        .map(|guard| &guard.name)
+        .chain(level.furnaces.iter().map(|furnace| &furnace.name))
+        .chain(level.doors.iter().map(|door| &door.name))
+        .chain(level.switches.iter().map(|switch| &switch.name))
+        .chain(level.items.iter().map(|item| &item.name))
+        .collect();
+    for (index, name) in names.iter().enumerate() {
+        // Report each duplicated name once, at its second use.
+        if names[..index].iter().filter(|other| *other == name).count() == 1 {
+            issues.push(LevelIssue::DuplicateName { name: name.to_string() });
+        }
+    }
+}
+
+/// Switches and doors refer to other objects by name, so check those exist.
+fn check_world_objects(level: &Level, issues: &mut Vec<LevelIssue>) {
+    for switch in &level.switches {
+        for door in &switch.doors {
+            if !level.doors.iter().any(|other| other.name == *door) {
+                issues.push(LevelIssue::UnknownDoor {
+                    switch: switch.name.clone(),
+                    door: door.clone(),
+                });
+            }
+        }
+    }
+    for door in &level.doors {
+        let Some(item) = &door.key else {
+            continue;
+        };
+        if !level.items.iter().any(|other| other.name == *item) {
+            issues.push(LevelIssue::UnknownKey {
+                door: door.name.clone(),
+                item: item.clone(),
+            });
+        }
+    }
+}
+
+/// Flood fill the floor from the player's start and check every furnace is close to it,
+/// and every guard that has to die for the level to be won walks by it.
+/// Doors start closed, and open once the fill gets to a switch for them, or to their
+/// key and then to them.
+fn check_reachability(level: &Level, grid: &NavGrid, issues: &mut Vec<LevelIssue>) {
+    let mut grid = grid.clone();
+    let mut open = vec![false; level.doors.len()];
+    let reached = loop {
+        let closed: Vec<Obstacle> = level
+            .doors
+            .iter()
+            .zip(&open)
+            .filter(|(_, &open)| !open)
+            .map(|(door, _)| door.obstacle())
+            .collect();
+        grid.set_doors(&closed);
+        let reached = grid.reachable_from(level.player_start.xz());
+        let near = |position: Vec2, reach: f32| is_near(&grid, &reached, position, reach);
+        let mut opened = false;
+        for (door, open) in level.doors.iter().zip(&mut open) {
+            if *open {
+                continue;
+            }
+            let switched = level.switches.iter().any(|switch| {
+                switch.doors.contains(&door.name) && near(switch.translation.xz(), REACH)
+            });
+            let unlocked = door.key.as_ref().is_some_and(|key| {
+                let obstacle = door.obstacle();
+                level
+                    .items
+                    .iter()
+                    .any(|item| item.name == *key && near(item.translation.xz(), REACH))
+                    && grid.cells().any(|cell| {
+                        reached[grid.index(cell)]
+                            && obstacle
+                                .penetration(grid.center(cell), CHARACTER_RADIUS + REACH)
+                                .is_some()
+                    })
+            });
+            if switched || unlocked {
+                *open = true;
+                opened = true;
+            }
+        }
+        if !opened {
+            break reached;
+        }
+    };
+    let near = |position: Vec2, reach: f32| is_near(&grid, &reached, position, reach);
+    for furnace in &level.furnaces {
+        if !near(furnace.translation.xz(), FURNACE_REACH) {
+            issues.push(LevelIssue::UnreachableFurnace { furnace: furnace.name.clone() });
+        }
+    }
+    for guard in level.guards.iter().filter(|guard| guard.kind.can_be_killed()) {
+        if !guard.path.iter().any(|(_, point)| near(point.xz(), KILL_RANGE)) {
+            issues.push(LevelIssue::UnreachableGuard { guard: guard.name.clone() });
+        }
+    }
+}
+
+/// Whether any `reached` cell is closer than `reach` to `position`.
+fn is_near(grid: &NavGrid, reached: &[bool], position: Vec2, reach: f32) -> bool {
+    grid.cells()
+        .any(|cell| reached[grid.index(cell)] && grid.center(cell).distance(position) < reach)
+}
+
+/// Levels are won by killing every guard that can die, so there has to be one.
+fn check_win(level: &Level, issues: &mut Vec<LevelIssue>) {
+    if !level.guards.iter().any(|guard| guard.kind.can_be_killed()) {
+        issues.push(LevelIssue::NothingToKill);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{
+        ghosts::DEFAULT_MAX_GHOSTS,
+        guards::{GuardKind, DEFAULT_ALARM_BODIES},
+        level::{DoorData, FurnaceData, GuardData, ItemData, SwitchData, WallData},
+        paradox::ParadoxRule,
+        world::Persistence,
+    };
+
+    fn wall(x: f32, z: f32, half_x: f32, half_z: f32) -> WallData {
+        WallData {
+            translation: Vec3::new(x, 2.0, z),
+            scale: Vec3::new(half_x, 2.0, half_z),
+            rotation: 0.0,
+            shape: WallShape::Box,
+        }
+    }
+
+    /// A square room from -10 to 10, with the player and a guard in the west half
+    /// and a furnace in the east half.
+    fn room() -> Level {
+        Level {
+            name: "Test".to_string(),
+            max_time: 20.0,
+            player_start: Vec3::new(-5.0, 0.0, -5.0),
+            walls: vec![
+                wall(0.0, -10.0, 10.5, 0.5),
+                wall(0.0, 10.0, 10.5, 0.5),
+                wall(-10.0, 0.0, 0.5, 10.5),
+                wall(10.0, 0.0, 0.5, 10.5),
+            ],
+            furnaces: vec![FurnaceData {
+                name: "Furnace".to_string(),
+                translation: Vec3::new(5.0, 1.0, -5.0),
+            }],
+            guards: vec![GuardData {
+                name: "Guard".to_string(),
+                kind: GuardKind::Guard,
+                path: vec![(0.0, Vec3::new(-7.0, 0.0, 5.0)), (10.0, Vec3::new(-3.0, 0.0, 5.0))],
+            }],
+            paradox: ParadoxRule::default(),
+            max_ghosts: DEFAULT_MAX_GHOSTS,
+            alarm_bodies: DEFAULT_ALARM_BODIES,
+            doors: vec![],
+            switches: vec![],
+            items: vec![],
+        }
+    }
+
+    /// The room split in half by a wall at x = 0, with a closed door in the middle of it.
+    fn split_room(key: Option<&str>) -> Level {
+        let mut level = room();
+        level.walls.push(wall(0.0, -5.75, 0.5, 4.25));
+        level.walls.push(wall(0.0, 5.75, 0.5, 4.25));
+        level.doors.push(DoorData {
+            name: "Door".to_string(),
+            translation: Vec3::new(0.0, 2.0, 0.0),
+            scale: Vec3::new(0.5, 2.0, 1.5),
+            rotation: 0.0,
+            key: key.map(str::to_string),
+            persistence: Persistence::Reset,
+        });
+        level
+    }
+
+    fn item(name: &str, x: f32, z: f32) -> ItemData {
+        ItemData {
+            name: name.to_string(),
+            translation: Vec3::new(x, 0.5, z),
+            persistence: Persistence::Reset,
+        }
+    }
+
+    #[test]
+    fn a_plain_room_has_no_issues() {
+        assert_eq!(validate_level(&room()), vec![]);
+    }
+
+    #[test]
+    fn waypoints_are_checked() {
+        let mut level = room();
+        level.guards[0].path = vec![
+            (5.0, Vec3::new(-7.0, 0.0, 5.0)),
+            (2.0, Vec3::new(-3.0, 0.0, 5.0)),
+            (25.0, Vec3::new(-3.0, 0.0, 0.0)),
+        ];
+        level.guards.push(GuardData {
+            name: "Lost".to_string(),
+            kind: GuardKind::Guard,
+            path: vec![],
+        });
+        let issues = validate_level(&level);
+        assert!(issues.contains(&LevelIssue::WaypointOutOfOrder {
+            guard: "Guard".to_string(),
+            index: 1,
+            time: 2.0,
+            previous: 5.0,
+        }));
+        assert!(issues.contains(&LevelIssue::WaypointOutOfLoop {
+            guard: "Guard".to_string(),
+            index: 2,
+            time: 25.0,
+            max_time: 20.0,
+        }));
+        assert!(issues.contains(&LevelIssue::EmptyPath { guard: "Lost".to_string() }));
+    }
+
+    #[test]
+    fn no_route_between_waypoints() {
+        let mut level = room();
+        level.walls.push(wall(0.0, 0.0, 0.5, 10.0));
+        level.guards[0].path[1].1 = Vec3::new(5.0, 0.0, 5.0);
+        let issues = validate_level(&level);
+        let guard = "Guard".to_string();
+        assert!(issues.contains(&LevelIssue::NoRoute { guard: guard.clone(), from: 0, to: 1 }));
+        assert!(issues.contains(&LevelIssue::NoRoute { guard, from: 1, to: 0 }));
+
+        // Cameras never walk, so their waypoints don't need a route.
+        level.guards[0].kind = GuardKind::Camera;
+        level.guards.push(room().guards[0].clone());
+        level.guards[1].name = "Walker".to_string();
+        assert!(!validate_level(&level)
+            .iter()
+            .any(|issue| matches!(issue, LevelIssue::NoRoute { .. })));
+    }
+
+    #[test]
+    fn broken_walls() {
+        let mut level = room();
+        level.walls.push(level.walls[1].clone());
+        level.walls.push(wall(-5.0, -5.0, 1.0, 1.0));
+        level.walls.push(WallData {
+            shape: WallShape::Polygon(vec![
+                Vec2::new(0.0, 0.0),
+                Vec2::new(2.0, 0.0),
+                Vec2::new(1.0, 0.5),
+                Vec2::new(2.0, 2.0),
+                Vec2::new(0.0, 2.0),
+            ]),
+            ..wall(5.0, 5.0, 1.0, 1.0)
+        });
+        let issues = validate_level(&level);
+        assert!(issues.contains(&LevelIssue::DuplicateWall { wall: 4, original: 1 }));
+        assert!(issues.contains(&LevelIssue::PlayerInsideWall { wall: 5 }));
+        assert!(issues.contains(&LevelIssue::InvalidWallShape { wall: 6 }));
+    }
+
+    #[test]
+    fn furnace_behind_a_closed_door_is_unreachable() {
+        let unreachable = LevelIssue::UnreachableFurnace { furnace: "Furnace".to_string() };
+        assert_eq!(validate_level(&split_room(None)), vec![unreachable.clone()]);
+
+        let mut switched = split_room(None);
+        switched.switches.push(SwitchData {
+            name: "Switch".to_string(),
+            translation: Vec3::new(-3.0, 0.0, -3.0),
+            doors: vec!["Door".to_string()],
+            persistence: Persistence::Reset,
+        });
+        assert_eq!(validate_level(&switched), vec![]);
+
+        let mut unlocked = split_room(Some("Key"));
+        unlocked.items.push(item("Key", -3.0, -3.0));
+        assert_eq!(validate_level(&unlocked), vec![]);
+
+        let mut locked_in = split_room(Some("Key"));
+        locked_in.items.push(item("Key", 5.0, 5.0));
+        assert_eq!(validate_level(&locked_in), vec![unreachable]);
+    }
+
+    #[test]
+    fn duplicate_and_missing_names() {
+        let mut level = split_room(Some("Missing key"));
+        level.furnaces.push(level.furnaces[0].clone());
+        level.switches.push(SwitchData {
+            name: "Switch".to_string(),
+            translation: Vec3::new(-3.0, 0.0, -3.0),
+            doors: vec!["Door".to_string(), "Missing door".to_string()],
+            persistence: Persistence::Reset,
+        });
+        let issues = validate_level(&level);
+        assert!(issues.contains(&LevelIssue::DuplicateName { name: "Furnace".to_string() }));
+        assert!(issues.contains(&LevelIssue::UnknownDoor {
+            switch: "Switch".to_string(),
+            door: "Missing door".to_string(),
+        }));
+        assert!(issues.contains(&LevelIssue::UnknownKey {
+            door: "Door".to_string(),
+            item: "Missing key".to_string(),
+        }));
+    }
+
+    #[test]
+    fn levels_that_cannot_be_won() {
+        let mut cameras_only = room();
+        cameras_only.guards[0].kind = GuardKind::Camera;
+        assert_eq!(validate_level(&cameras_only), vec![LevelIssue::NothingToKill]);
+
+        // A guard locked in the east half, whose only door nobody can open.
+        let mut locked_away = split_room(Some("Key"));
+        locked_away.items.push(item("Key", 5.0, 5.0));
+        locked_away.furnaces.clear();
+        locked_away.guards[0].path = vec![(0.0, Vec3::new(5.0, 0.0, 5.0))];
+        assert_eq!(
+            validate_level(&locked_away),
+            vec![LevelIssue::UnreachableGuard { guard: "Guard".to_string() }],
+        );
+    }
+}
@@ -0,0 +1,439 @@
+//! What guards can see, and showing it.
+//! Sight is a cone in front of a guard that walls cut short. The same rays
+//! answer whether a guard sees something, draw the cone on the floor in the
+//! colour of the guard's [`GuardState`], and draw the debug overlay toggled
+//! with F3.
+//! How far a guard sees someone depends on how much light falls on them.
+//! The only lights are furnaces, brighter the longer nobody tends them, and
+//! the lamp over the player, so ghosts away from both are hard to spot. The
+//! lamp lights up everyone around the player but not the player, who is only
+//! as easy to spot as the furnaces make them.
+
+use std::{borrow::Borrow, f32::consts::PI};
+
+use bevy::{
+    prelude::*,
+    render::{
+        mesh::{Indices, PrimitiveTopology},
+        render_asset::RenderAssetUsages,
+    },
+};
+
+use super::{
+    collision::Obstacle,
+    guards::{GuardAi, GuardKind, GuardState},
+    interpolation::interpolate,
+    movement::{IsDead, Npc},
+    spatial::WallIndex,
+    spawn::{
+        player::{LAMP_HEIGHT, LAMP_INTENSITY, LAMP_RANGE},
+        stage::{Furnace, FURNACE_RANGE},
+    },
+};
+use crate::{screen::InGame, AppSet};
+
+pub(super) fn plugin(app: &mut App) {
+    app.register_type::<VisionCone>();
+    app.register_type::<VisionDebug>();
+    app.init_resource::<VisionDebug>();
+
+    app.add_systems(
+        Update,
+        (
+            toggle_vision_debug.in_set(AppSet::RecordInput),
+            (spawn_vision_cones, update_vision_cones, draw_vision_debug)
+                .chain()
+                .after(interpolate)
+                .in_set(AppSet::Update),
+        )
+            .run_if(in_state(InGame)),
+    );
+}
+
+/// Half the angle regular guards see in front of them, other kinds have their own
+/// [`Profile`](super::guards::Profile).
+pub const VIEW_HALF_ANGLE: f32 = PI / 4.0;
+/// How far regular guards see someone in full light, and how far their cones are drawn.
+pub const VIEW_DISTANCE: f32 = 20.0;
+/// Part of its view distance a guard still sees someone standing in the dark.
+const DARK_VIEW_FACTOR: f32 = 0.35;
+/// How much slower the meter fills for someone in the dark, and faster in full light.
+const DARK_FILL_FACTOR: f32 = 0.4;
+const BRIGHT_FILL_FACTOR: f32 = 2.0;
+/// Light, in lux, below which someone counts as in the dark, and above which in full light.
+/// Exposure is logarithmic in between, like eyes are.
+const DARK_LUX: f32 = 1_000.0;
+const BRIGHT_LUX: f32 = 1_000_000.0;
+/// Rays per cone. More of them follow walls more closely.
+const RAYS: usize = 32;
+/// Height of the cones above the floor, so they don't flicker into it.
+const CONE_HEIGHT: f32 = 0.03;
+
+/// The floor a guard can see, as a fan of points around `eye`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ViewCone {
+    pub eye: Vec2,
+    /// Where each ray stops, from one edge of the cone to the other.
+    pub points: Vec<Vec2>,
+}
+
+impl ViewCone {
+    pub fn new(
+        eye: &Transform,
+        half_angle: f32,
+        distance: f32,
+        obstacles: &[impl Borrow<Obstacle>],
+    ) -> Self {
+        let origin = eye.translation.xz();
+        let forward = eye.forward().xz().normalize_or_zero();
+        let points = (0..=RAYS)
+            .map(|ray| {
+                let angle = -half_angle + 2.0 * half_angle * ray as f32 / RAYS as f32;
+                let direction = Vec2::from_angle(angle).rotate(forward);
+                origin + direction * ray_distance(origin, direction, distance, obstacles)
+            })
+            .collect();
+        Self {
+            eye: origin,
+            points,
+        }
+    }
+}
+
+/// How far a ray goes before a wall stops it, at most `distance`.
+pub fn ray_distance(
+    origin: Vec2,
+    direction: Vec2,
+    distance: f32,
+    obstacles: &[impl Borrow<Obstacle>],
+) -> f32 {
+    obstacles
+        .iter()
+        .filter_map(|obstacle| obstacle.borrow().ray_hit(origin, direction))
+        .fold(distance, f32::min)
+}
+
+/// Whether nothing blocks the floor between `from` and `to`.
+pub fn is_unblocked(from: Vec2, to: Vec2, obstacles: &[impl Borrow<Obstacle>]) -> bool {
+    let distance = from.distance(to);
+    distance < f32::EPSILON
+        || ray_distance(from, (to - from) / distance, distance, obstacles) >= distance
+}
+
+/// Whether a guard standing at `eye` can see `target`, up to `range` away.
+pub fn can_see(
+    eye: &Transform,
+    target: Vec3,
+    half_angle: f32,
+    range: f32,
+    obstacles: &[impl Borrow<Obstacle>],
+) -> bool {
+    let offset = (target - eye.translation).xz();
+    if offset.length() > range {
+        return false;
+    }
+    offset.length() < f32::EPSILON
+        || offset.angle_between(eye.forward().xz()).abs() < half_angle
+            && is_unblocked(eye.translation.xz(), target.xz(), obstacles)
+}
+
+/// Something that lights up whoever stands near it.
+/// Matches the `PointLight` it stands for, but comes from gameplay state so
+/// detection plays out the same in every run.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Light {
+    pub position: Vec3,
+    /// In lumens, like `PointLight::intensity`.
+    pub intensity: f32,
+    pub range: f32,
+    /// Whoever carries it around, which it doesn't give away.
+    pub carrier: Option<Entity>,
+}
+
+impl Light {
+    pub fn furnace(transform: &Transform, furnace: &Furnace) -> Self {
+        Self {
+            position: transform.translation,
+            intensity: furnace.intensity(),
+            range: FURNACE_RANGE,
+            carrier: None,
+        }
+    }
+
+    /// The lamp that hangs over the player.
+    pub fn lamp(player: Entity, transform: &Transform) -> Self {
+        Self {
+            position: transform.translation + Vec3::Y * LAMP_HEIGHT,
+            intensity: LAMP_INTENSITY,
+            range: LAMP_RANGE,
+            carrier: Some(player),
+        }
+    }
+
+    /// Lux falling on `target`, walls casting shadows along the floor.
+    fn illuminance(&self, target: Vec3, walls: &WallIndex) -> f32 {
+        let distance = self.position.distance(target);
+        if distance > self.range {
+            return 0.0;
+        }
+        let (from, to) = (self.position.xz(), target.xz());
+        if !is_unblocked(from, to, &walls.along(from, to)) {
+            return 0.0;
+        }
+        self.intensity / (4.0 * PI * distance.max(1.0).powi(2))
+    }
+}
+
+/// Lights from furnaces and from the lamps over players.
+pub fn lights<'a>(
+    furnaces: impl Iterator<Item = (&'a Transform, &'a Furnace)>,
+    players: impl Iterator<Item = (Entity, &'a Transform)>,
+) -> Vec<Light> {
+    furnaces
+        .map(|(transform, furnace)| Light::furnace(transform, furnace))
+        .chain(players.map(|(player, transform)| Light::lamp(player, transform)))
+        .collect()
+}
+
+/// How lit `target` is, from 0 in the dark to 1 in full light.
+/// Lights `target_id` carries don't count, it is only seen by the others.
+pub fn exposure(
+    target: Vec3,
+    target_id: Option<Entity>,
+    lights: &[Light],
+    walls: &WallIndex,
+) -> f32 {
+    let lux: f32 = lights
+        .iter()
+        .filter(|light| target_id.is_none() || light.carrier != target_id)
+        .map(|light| light.illuminance(target, walls))
+        .sum();
+    ((lux.max(1.0) / DARK_LUX).log10() / (BRIGHT_LUX / DARK_LUX).log10()).clamp(0.0, 1.0)
+}
+
+/// How far a guard that sees `view_distance` in full light sees someone with this [`exposure`].
+pub fn sight_range(view_distance: f32, exposure: f32) -> f32 {
+    view_distance * DARK_VIEW_FACTOR.lerp(1.0, exposure)
+}
+
+/// How much faster than usual someone with this [`exposure`] fills the meter.
+pub fn light_fill_factor(exposure: f32) -> f32 {
+    DARK_FILL_FACTOR.lerp(BRIGHT_FILL_FACTOR, exposure)
+}
+
+/// The cone drawn on the floor for `guard`.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Reflect)]
+#[reflect(Component)]
+pub struct VisionCone {
+    pub guard: Entity,
+}
+
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq, Default, Reflect)]
+#[reflect(Resource)]
+pub struct VisionDebug(pub bool);
+
+pub fn state_color(state: GuardState) -> Color {
+    match state {
+        GuardState::Patrol => Color::srgba(0.3, 0.9, 0.3, 0.2),
+        GuardState::Suspicious => Color::srgba(1.0, 0.9, 0.2, 0.3),
+        GuardState::Investigate => Color::srgba(1.0, 0.5, 0.1, 0.3),
+        GuardState::Alert => Color::srgba(1.0, 0.1, 0.1, 0.4),
+        GuardState::Return => Color::srgba(0.3, 0.6, 1.0, 0.2),
+    }
+}
+
+fn toggle_vision_debug(input: Res<ButtonInput<KeyCode>>, mut debug: ResMut<VisionDebug>) {
+    if input.just_pressed(KeyCode::F3) {
+        debug.0 = !debug.0;
+    }
+}
+
+fn spawn_vision_cones(
+    guards: Query<Entity, Added<GuardAi>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut commands: Commands,
+) {
+    for guard in &guards {
+        commands.spawn((
+            Name::new("Vision Cone"),
+            MaterialMeshBundle {
+                mesh: meshes.add(cone_mesh(&ViewCone {
+                    eye: Vec2::ZERO,
+                    points: vec![],
+                })),
+                material: materials.add(StandardMaterial {
+                    base_color: state_color(GuardState::Patrol),
+                    alpha_mode: AlphaMode::Blend,
+                    unlit: true,
+                    cull_mode: None,
+                    ..default()
+                }),
+                ..default()
+            },
+            VisionCone { guard },
+            StateScoped(InGame),
+        ));
+    }
+}
+
+/// Follow where guards are drawn rather than where they are on the last tick.
+fn update_vision_cones(
+    guards: Query<(&Transform, &GuardKind, &GuardAi, Has<IsDead>), With<Npc>>,
+    walls: Res<WallIndex>,
+    mut cones: Query<(
+        Entity,
+        &VisionCone,
+        &Handle<Mesh>,
+        &Handle<StandardMaterial>,
+        &mut Visibility,
+    )>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut commands: Commands,
+) {
+    for (entity, cone, mesh, material, mut visibility) in &mut cones {
+        let Ok((transform, kind, ai, is_dead)) = guards.get(cone.guard) else {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        };
+        if is_dead {
+            *visibility = Visibility::Hidden;
+            continue;
+        }
+        *visibility = Visibility::Inherited;
+        let profile = kind.profile();
+        let view = ViewCone::new(
+            transform,
+            profile.view_half_angle,
+            profile.view_distance,
+            &walls.near(transform.translation.xz(), profile.view_distance),
+        );
+        if let Some(mesh) = meshes.get_mut(mesh) {
+            *mesh = cone_mesh(&view);
+        }
+        if let Some(material) = materials.get_mut(material) {
+            material.base_color = state_color(ai.state);
+        }
+    }
+}
+
+fn draw_vision_debug(
+    debug: Res<VisionDebug>,
+    guards: Query<(&Transform, &GuardKind, &GuardAi), (With<Npc>, Without<IsDead>)>,
+    walls: Res<WallIndex>,
+    mut gizmos: Gizmos,
+) {
+    if !debug.0 {
+        return;
+    }
+    for (transform, kind, ai) in &guards {
+        let profile = kind.profile();
+        let view = ViewCone::new(
+            transform,
+            profile.view_half_angle,
+            profile.view_distance,
+            &walls.near(transform.translation.xz(), profile.view_distance),
+        );
+        let color = state_color(ai.state).with_alpha(1.0);
+        let at_height = |point: Vec2| Vec3::new(point.x, CONE_HEIGHT, point.y);
+        for &point in &view.points {
+            gizmos.line(at_height(view.eye), at_height(point), color.with_alpha(0.3));
+        }
+        gizmos.linestrip(view.points.iter().map(|&point| at_height(point)), color);
+        // The meter, as a bar over the guard's head.
+        let head = transform.translation + Vec3::Y * 2.5;
+        gizmos.line(head, head + Vec3::X * ai.detection * 2.0, color);
+        if ai.state != GuardState::Patrol {
+            gizmos.sphere(ai.last_seen, Quat::IDENTITY, 0.3, color);
+        }
+    }
+}
+
+
+/// A flat fan from the eye through every point of the cone.
+fn cone_mesh(view: &ViewCone) -> Mesh {
+    let positions: Vec<[f32; 3]> = [view.eye]
+        .iter()
+        .chain(&view.points)
+        .map(|point| [point.x, CONE_HEIGHT, point.y])
+        .collect();
+    let normals = vec![[0.0, 1.0, 0.0]; positions.len()];
+    let indices = (1..view.points.len() as u32)
+        .flat_map(|index| [0, index, index + 1])
+        .collect();
+    Mesh::new(
+        PrimitiveTopology::TriangleList,
+        RenderAssetUsages::default(),
+    )
+    .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, positions)
+    .with_inserted_attribute(Mesh::ATTRIBUTE_NORMAL, normals)
+    .with_inserted_indices(Indices::U32(indices))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::collision::Collider;
+
+    /// A furnace nobody tended for a while, a metre off the floor at the origin.
+    fn furnace() -> Light {
+        Light::furnace(&Transform::from_xyz(0.0, 1.0, 0.0), &Furnace { countdown: 25.0 })
+    }
+
+    #[test]
+    fn nobody_is_lit_without_lights() {
+        let walls = WallIndex::default();
+        assert_eq!(exposure(Vec3::ZERO, None, &[], &walls), 0.0);
+        assert_eq!(sight_range(VIEW_DISTANCE, 0.0), VIEW_DISTANCE * DARK_VIEW_FACTOR);
+    }
+
+    #[test]
+    fn lamp_lights_others_but_not_its_carrier() {
+        let walls = WallIndex::default();
+        let player = Entity::from_raw(1);
+        let lights = [Light::lamp(player, &Transform::IDENTITY)];
+        assert_eq!(exposure(Vec3::ZERO, Some(player), &lights, &walls), 0.0);
+
+        let ghost = Vec3::new(2.0, 0.0, 0.0);
+        let lit = exposure(ghost, Some(Entity::from_raw(2)), &lights, &walls);
+        assert!(lit > 0.3, "{lit}");
+        assert!(sight_range(VIEW_DISTANCE, lit) > VIEW_DISTANCE * DARK_VIEW_FACTOR);
+    }
+
+    #[test]
+    fn furnace_light_fades_with_distance() {
+        let walls = WallIndex::default();
+        let lights = [furnace()];
+        let exposures: Vec<f32> = [2.0, 6.0, 20.0]
+            .into_iter()
+            .map(|distance| exposure(Vec3::new(distance, 0.0, 0.0), None, &lights, &walls))
+            .collect();
+        assert!(exposures[0] > 0.5, "{exposures:?}");
+        assert!(exposures[0] > exposures[1] && exposures[1] > exposures[2], "{exposures:?}");
+        assert_eq!(exposures[2], 0.0);
+
+        let ranges: Vec<f32> =
+            exposures.iter().map(|&exposure| sight_range(VIEW_DISTANCE, exposure)).collect();
+        assert!(ranges[0] > ranges[1] && ranges[1] > ranges[2], "{ranges:?}");
+        assert!(ranges[0] <= VIEW_DISTANCE);
+    }
+
+    #[test]
+    fn walls_shadow_the_furnace() {
+        let mut walls = WallIndex::default();
+        walls.add(Obstacle::new(
+            &Collider::Cuboid { half_size: Vec2::new(0.5, 3.0) },
+            Vec3::new(2.0, 0.0, 0.0),
+            Quat::IDENTITY,
+        ));
+        let lights = [furnace()];
+        assert_eq!(exposure(Vec3::new(4.0, 0.0, 0.0), None, &lights, &walls), 0.0);
+        assert!(exposure(Vec3::new(0.0, 0.0, 4.0), None, &lights, &walls) > 0.0);
+    }
+
+    #[test]
+    fn full_light_gives_full_sight() {
+        assert_eq!(sight_range(VIEW_DISTANCE, 1.0), VIEW_DISTANCE);
+    }
+}
@@ -0,0 +1,258 @@
+//! Things in a level that loops can change: doors, the switches that open
+//! them and items that unlock them.
+//! Each of these has a [`Persistence`] saying whether it goes back to how
+//! the level starts it when a new loop begins, or stays the way the last
+//! loop left it.
+//!
+//! The rest of the level has fixed rules at a loop boundary:
+//! - Guard paths follow the loop's clock, so patrolling guards start their
+//!   routes over. Guards that were suspicious or alerted stay that way.
+//! - The alarm and the bodies guards have found start over, see
+//!   [`guards`](super::guards).
+//! - Scent trails are cleared, see [`scent`](super::scent).
+//! - Dead guards stay dead. Only rewinding brings them back.
+//! - Untended furnaces keep heating up across loops. Only standing next to
+//!   one cools it down again.
+//!
+//! Items carried by the player follow the item, so a kept key stays in the
+//! player's hands. Ghosts go back to the start of their loop every loop,
+//! carrying what the player had when that loop began.
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    collision::{Collider, Obstacle, CHARACTER_RADIUS},
+    movement::{Ghost, IsDead},
+    navigation::NavGrid,
+    recording::GhostRecording,
+    spawn::player::Player,
+};
+use crate::{screen::InGame, FixedSet};
+
+pub(super) fn plugin(app: &mut App) {
+    app.register_type::<Persistence>();
+    app.register_type::<WorldObject>();
+    app.register_type::<Door>();
+    app.register_type::<Switch>();
+    app.register_type::<Item>();
+    app.register_type::<Carrying>();
+
+    app.observe(reset_world);
+    app.add_systems(
+        FixedUpdate,
+        (
+            // Rewinds and loads change objects outside of `React`, so catch up before moving.
+            sync_doors.after(FixedSet::Input).before(FixedSet::Move),
+            (press_switches, take_items, unlock_doors)
+                .chain()
+                .in_set(FixedSet::React),
+        )
+            .run_if(in_state(InGame)),
+    );
+    app.add_systems(Update, show_world_objects.run_if(in_state(InGame)));
+}
+
+/// How close a character needs to get to a switch or item to use it,
+/// or to a door to unlock it.
+pub const REACH: f32 = 1.0;
+
+/// What happens to a [`WorldObject`] when a new loop begins.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Reflect, Serialize, Deserialize)]
+pub enum Persistence {
+    /// Back to how the level starts it, like everything else in the loop.
+    #[default]
+    Reset,
+    /// Stays the way the last loop left it.
+    Keep,
+}
+
+/// Something a loop can change. It only changes one way, from unused to used:
+/// opened for doors, pressed for switches and taken for items.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Reflect)]
+#[reflect(Component)]
+pub struct WorldObject {
+    pub persistence: Persistence,
+    pub used: bool,
+}
+
+/// Blocks the way until it is opened, by a [`Switch`] or with its key.
+#[derive(Component, Debug, Clone, PartialEq, Reflect)]
+#[reflect(Component)]
+pub struct Door {
+    /// Name of the [`Item`] that opens it.
+    pub key: Option<String>,
+    /// The shape it blocks while closed, taken away while open.
+    pub collider: Collider,
+}
+
+/// Opens doors when someone steps on it.
+#[derive(Component, Debug, Clone, PartialEq, Eq, Reflect)]
+#[reflect(Component)]
+pub struct Switch {
+    /// Names of the doors it opens.
+    pub doors: Vec<String>,
+}
+
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Default, Reflect)]
+#[reflect(Component)]
+pub struct Item;
+
+/// Names of the items a player or ghost picked up.
+#[derive(Component, Debug, Clone, PartialEq, Eq, Default, Reflect)]
+#[reflect(Component)]
+pub struct Carrying(pub Vec<String>);
+
+/// A new loop has begun, sent by [`loop_time`](super::movement::loop_time).
+#[derive(Event, Debug)]
+pub struct LoopStarted {
+    pub gen: u16,
+}
+
+fn reset_world(
+    trigger: Trigger<LoopStarted>,
+    mut objects: Query<(&Name, &mut WorldObject, Has<Item>)>,
+    mut player: Query<&mut Carrying, With<Player>>,
+    mut ghosts: Query<(&Ghost, &mut Carrying), (Without<Player>, Without<IsDead>)>,
+    mut recording: ResMut<GhostRecording>,
+) {
+    // Ghosts start their loop again with what they had when it began.
+    for (ghost, mut carrying) in &mut ghosts {
+        if let Some(recorded) = recording.loops.get(usize::from(ghost.gen)) {
+            carrying.0.clone_from(&recorded.carrying);
+        }
+    }
+
+    let mut returned = vec![];
+    for (name, mut object, is_item) in &mut objects {
+        if object.persistence == Persistence::Reset && object.used {
+            object.used = false;
+            if is_item {
+                returned.push(name.to_string());
+            }
+        }
+    }
+    let Ok(mut carrying) = player.get_single_mut() else {
+        return;
+    };
+    carrying.0.retain(|item| !returned.contains(item));
+    // The ghost of this loop will start out with the same things.
+    if let Some(started) = recording.loops.get_mut(usize::from(trigger.event().gen)) {
+        started.carrying = carrying.0.clone();
+    }
+}
+
+/// Stepping on a switch presses it, and a pressed switch holds its doors open.
+fn press_switches(
+    characters: Query<&Transform, (Or<(With<Player>, With<Ghost>)>, Without<IsDead>)>,
+    switches: Query<(Entity, &Transform, &Switch)>,
+    mut objects: Query<(&Name, &mut WorldObject)>,
+) {
+    for (entity, switch_transform, switch) in &switches {
+        let Ok((_, mut object)) = objects.get_mut(entity) else {
+            continue;
+        };
+        let stepped_on = characters.iter().any(|transform| {
+            transform.translation.xz().distance(switch_transform.translation.xz()) < REACH
+        });
+        if stepped_on && !object.used {
+            object.used = true;
+        }
+        let pressed = object.used;
+        if !pressed {
+            continue;
+        }
+        for (name, mut door) in &mut objects {
+            if !door.used && switch.doors.iter().any(|opens| opens == name.as_str()) {
+                door.used = true;
+            }
+        }
+    }
+}
+
+fn take_items(
+    mut characters: Query<(&Transform, &mut Carrying), Without<IsDead>>,
+    mut items: Query<(&Name, &Transform, &mut WorldObject), With<Item>>,
+) {
+    for (name, item_transform, mut object) in &mut items {
+        if object.used {
+            continue;
+        }
+        let taker = characters.iter_mut().find(|(transform, _)| {
+            transform.translation.xz().distance(item_transform.translation.xz()) < REACH
+        });
+        if let Some((_, mut carrying)) = taker {
+            object.used = true;
+            carrying.0.push(name.to_string());
+        }
+    }
+}
+
+fn unlock_doors(
+    characters: Query<(&Transform, &Carrying), Without<IsDead>>,
+    mut doors: Query<(&Door, &Transform, &mut WorldObject)>,
+) {
+    for (door, door_transform, mut object) in &mut doors {
+        let Some(key) = &door.key else {
+            continue;
+        };
+        if object.used {
+            continue;
+        }
+        let obstacle = Obstacle::from_transform(&door.collider, door_transform);
+        let unlocked = characters.iter().any(|(transform, carrying)| {
+            carrying.0.contains(key)
+                && obstacle
+                    .penetration(transform.translation.xz(), CHARACTER_RADIUS + REACH)
+                    .is_some()
+        });
+        if unlocked {
+            object.used = true;
+        }
+    }
+}
+
+/// Open doors stop blocking, closed ones start again, for guards finding their way too.
+pub fn sync_doors(
+    changed: Query<(Entity, &Door, &WorldObject, Has<Collider>), Changed<WorldObject>>,
+    doors: Query<(&Door, &Transform, &WorldObject)>,
+    mut grid: ResMut<NavGrid>,
+    mut commands: Commands,
+) {
+    if changed.is_empty() {
+        return;
+    }
+    for (entity, door, object, has_collider) in &changed {
+        if object.used && has_collider {
+            commands.entity(entity).remove::<Collider>();
+        } else if !object.used && !has_collider {
+            commands.entity(entity).insert(door.collider.clone());
+        }
+    }
+    let closed: Vec<Obstacle> = doors
+        .iter()
+        .filter(|(_, _, object)| !object.used)
+        .map(|(door, transform, _)| Obstacle::from_transform(&door.collider, transform))
+        .collect();
+    grid.set_doors(&closed);
+}
+
+fn show_world_objects(
+    mut objects: Query<
+        (&WorldObject, &mut Visibility, &mut Transform, Has<Switch>),
+        Changed<WorldObject>,
+    >,
+) {
+    for (object, mut visibility, mut transform, is_switch) in &mut objects {
+        if is_switch {
+            // Pressed switches sink into the floor.
+            transform.translation.y = if object.used { -0.05 } else { 0.05 };
+            continue;
+        }
+        *visibility = if object.used {
+            Visibility::Hidden
+        } else {
+            Visibility::Inherited
+        };
+    }
+}
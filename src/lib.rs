@@ -8,6 +8,13 @@ use bevy::{
     asset::AssetMetaCheck, audio::{AudioPlugin, Volume}, pbr::ShadowFilteringMethod, prelude::*
 };
 
+pub use game::{
+    collision::{move_and_slide, Collider, Obstacle, CHARACTER_RADIUS},
+    level::Level,
+    spatial::SpatialGrid,
+    validate::{validate_level, LevelIssue},
+};
+
 pub struct AppPlugin;
 
 impl Plugin for AppPlugin {
@@ -17,6 +24,10 @@ impl Plugin for AppPlugin {
             Update,
             (AppSet::TickTimers, AppSet::RecordInput, AppSet::Update).chain(),
         );
+        app.configure_sets(
+            FixedUpdate,
+            (FixedSet::Input, FixedSet::Move, FixedSet::React, FixedSet::Tick).chain(),
+        );
 
         // Spawn the main camera.
         app.add_systems(Startup, spawn_camera);
@@ -76,6 +87,20 @@ enum AppSet {
     Update,
 }
 
+/// Groupings of gameplay systems in the `FixedUpdate` schedule.
+/// Gameplay only runs there, so a loop plays out the same at any frame rate.
+#[derive(SystemSet, Debug, Clone, Copy, Eq, PartialEq, Hash)]
+enum FixedSet {
+    /// Record the player's input for this tick, or feed in a replay's.
+    Input,
+    /// Move characters.
+    Move,
+    /// React to where everyone ended up.
+    React,
+    /// Advance the time loop, once everything else has seen the current tick.
+    Tick,
+}
+
 fn spawn_camera(mut commands: Commands) {
     commands.spawn((
         Name::new("Camera"),
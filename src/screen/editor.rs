@@ -0,0 +1,234 @@
+//! The level editor screen, for authoring level files in game.
+
+use bevy::{input::common_conditions::input_just_pressed, prelude::*, ui::Val::*};
+
+use super::Screen;
+use crate::{
+    game::{
+        campaign::{CurrentLevel, CAMPAIGN},
+        editor::LevelEditor,
+        level::Level,
+    },
+    ui::prelude::*,
+};
+
+pub(super) fn plugin(app: &mut App) {
+    app.add_systems(OnEnter(Screen::Editor), enter_editor);
+    app.add_systems(OnExit(Screen::Editor), exit_editor);
+
+    app.add_systems(
+        Update,
+        (
+            update_editor_status,
+            update_timeline,
+            return_to_title_screen.run_if(input_just_pressed(KeyCode::Escape)),
+            playtest.run_if(input_just_pressed(KeyCode::F5)),
+        )
+            .run_if(in_state(Screen::Editor)),
+    );
+}
+
+/// Where levels that are not part of the campaign get saved.
+const CUSTOM_LEVEL_FILE: &str = "levels/custom.level.ron";
+
+#[derive(Component)]
+struct EditorStatus;
+
+#[derive(Component)]
+struct Timeline;
+
+#[derive(Component)]
+struct TimelineCursor;
+
+#[derive(Component)]
+struct TimelineWaypoint;
+
+fn enter_editor(
+    mut commands: Commands,
+    current_level: Res<CurrentLevel>,
+    levels: Res<Assets<Level>>,
+    mut camera: Query<&mut Transform, With<Camera3d>>,
+) {
+    let Some(level) = levels.get(&current_level.handle) else {
+        error!("Tried to edit a level before it was loaded");
+        return;
+    };
+    let file = match current_level.campaign_index {
+        Some(index) => CAMPAIGN[index].path(),
+        None => CUSTOM_LEVEL_FILE,
+    };
+    if let Ok(mut camera) = camera.get_single_mut() {
+        camera.translation = level.player_start + Vec3::new(-5.7, 20.7, -20.0);
+    }
+    commands.insert_resource(LevelEditor::new(level.clone(), file));
+
+    commands
+        .spawn((
+            Name::new("Editor UI"),
+            NodeBundle {
+                style: Style {
+                    width: Percent(100.0),
+                    height: Percent(100.0),
+                    justify_content: JustifyContent::SpaceBetween,
+                    align_items: AlignItems::Center,
+                    flex_direction: FlexDirection::Column,
+                    padding: UiRect::all(Px(10.0)),
+                    position_type: PositionType::Absolute,
+                    ..default()
+                },
+                ..default()
+            },
+            StateScoped(Screen::Editor),
+        ))
+        .with_children(|children| {
+            children.spawn((
+                Name::new("Editor Status"),
+                TextBundle::from_sections([
+                    TextSection::new(
+                        "",
+                        TextStyle {
+                            font_size: 24.0,
+                            color: ui_palette::LABEL_TEXT,
+                            ..default()
+                        },
+                    ),
+                    TextSection::new(
+                        "\n1 select  2 wall  3 furnace  4 guard  5 player start  6 door  7 switch  8 item | \
+                         LMB use tool  RMB deselect  Del delete  arrows resize  shift+arrows move  R rotate  T box/pillar, guard kind or reset/keep | \
+                         Q/E timeline  [/] loop length | WASD pan  wheel zoom | \
+                         Ctrl+S save  F5 playtest  Esc back",
+                        TextStyle {
+                            font_size: 16.0,
+                            color: ui_palette::LABEL_TEXT,
+                            ..default()
+                        },
+                    ),
+                ]),
+                EditorStatus,
+            ));
+            children
+                .spawn((
+                    Name::new("Timeline"),
+                    NodeBundle {
+                        style: Style {
+                            width: Percent(80.0),
+                            height: Px(20.0),
+                            ..default()
+                        },
+                        background_color: BackgroundColor(ui_palette::NODE_BACKGROUND),
+                        ..default()
+                    },
+                    Timeline,
+                ))
+                .with_children(|children| {
+                    children.spawn((
+                        Name::new("Timeline Cursor"),
+                        NodeBundle {
+                            style: Style {
+                                position_type: PositionType::Absolute,
+                                width: Px(3.0),
+                                height: Percent(100.0),
+                                ..default()
+                            },
+                            background_color: BackgroundColor(ui_palette::LABEL_TEXT),
+                            ..default()
+                        },
+                        TimelineCursor,
+                    ));
+                });
+        });
+}
+
+fn exit_editor(mut commands: Commands) {
+    commands.remove_resource::<LevelEditor>();
+}
+
+fn update_editor_status(
+    editor: Res<LevelEditor>,
+    mut status: Query<&mut Text, With<EditorStatus>>,
+) {
+    if !editor.is_changed() {
+        return;
+    }
+    let selection = editor.selection.map_or("nothing".to_string(), |selection| {
+        format!("{selection:?}")
+    });
+    for mut text in &mut status {
+        text.sections[0].value = format!(
+            "{} | tool: {:?} | selected: {selection} | time: {:.1} / {:.1} | {} walls, {} furnaces, {} guards, {} doors | {}",
+            editor.level.name,
+            editor.tool,
+            editor.time,
+            editor.level.max_time,
+            editor.level.walls.len(),
+            editor.level.furnaces.len(),
+            editor.level.guards.len(),
+            editor.level.doors.len(),
+            editor.file,
+        );
+    }
+}
+
+/// Show the timeline's time and the waypoint times of the selected guard.
+fn update_timeline(
+    mut commands: Commands,
+    editor: Res<LevelEditor>,
+    timeline: Query<Entity, With<Timeline>>,
+    mut cursor: Query<&mut Style, With<TimelineCursor>>,
+    waypoints: Query<Entity, With<TimelineWaypoint>>,
+) {
+    if !editor.is_changed() {
+        return;
+    }
+    let fraction = |time: f32| Percent(100.0 * time / editor.level.max_time);
+    for mut style in &mut cursor {
+        style.left = fraction(editor.time);
+    }
+    for entity in &waypoints {
+        commands.entity(entity).despawn_recursive();
+    }
+    let (Ok(timeline), Some(path)) = (timeline.get_single(), editor.selected_path()) else {
+        return;
+    };
+    commands.entity(timeline).with_children(|children| {
+        for (time, _) in path {
+            children.spawn((
+                Name::new("Timeline Waypoint"),
+                NodeBundle {
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        left: fraction(*time),
+                        width: Px(8.0),
+                        height: Percent(60.0),
+                        top: Percent(20.0),
+                        ..default()
+                    },
+                    background_color: BackgroundColor(ui_palette::BUTTON_TEXT),
+                    ..default()
+                },
+                TimelineWaypoint,
+            ));
+        }
+    });
+}
+
+fn return_to_title_screen(mut next_screen: ResMut<NextState<Screen>>) {
+    next_screen.set(Screen::Title);
+}
+
+/// Play the edits, even if they were not saved. They go in a level of their
+/// own so the campaign or generated level they started from stays untouched,
+/// and saves and replays of the playtest don't claim to be of that level.
+fn playtest(
+    mut next_screen: ResMut<NextState<Screen>>,
+    editor: Res<LevelEditor>,
+    mut current_level: ResMut<CurrentLevel>,
+    mut levels: ResMut<Assets<Level>>,
+) {
+    *current_level = CurrentLevel {
+        handle: levels.add(editor.level.clone()),
+        campaign_index: None,
+        seed: None,
+    };
+    next_screen.set(Screen::Playing);
+}
@@ -5,7 +5,7 @@ use bevy::prelude::*;
 use super::Screen;
 use crate::{
     game::{
-        assets::SoundtrackKey, audio::soundtrack::PlaySoundtrack,
+        assets::SoundtrackKey, audio::soundtrack::PlaySoundtrack, save::LoadRun,
     },
     ui::prelude::*
 };
@@ -24,6 +24,8 @@ pub(super) fn plugin(app: &mut App) {
 #[reflect(Component)]
 enum HellAction {
     Back,
+    /// Continue from the last save, usually the start of the loop you died in.
+    Load,
 }
 
 fn enter_hell(mut commands: Commands) {
@@ -35,6 +37,7 @@ fn enter_hell(mut commands: Commands) {
 
 
             children.button("escape").insert(HellAction::Back);
+            children.button("try again").insert(HellAction::Load);
         });    
     commands.trigger(PlaySoundtrack::Key(SoundtrackKey::Credits));
 }
@@ -44,6 +47,7 @@ fn exit_hell(mut commands: Commands) {
 }
 
 fn handle_hell_action(
+    mut commands: Commands,
     mut next_screen: ResMut<NextState<Screen>>,
     mut button_query: InteractionQuery<&HellAction>,
 ) {
@@ -51,6 +55,7 @@ fn handle_hell_action(
         if matches!(interaction, Interaction::Pressed) {
             match action {
                 HellAction::Back => next_screen.set(Screen::Playing),
+                HellAction::Load => commands.trigger(LoadRun),
             }
         }
     }
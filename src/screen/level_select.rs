@@ -0,0 +1,152 @@
+//! A screen to pick any unlocked level of the campaign, or a generated one.
+
+use bevy::{
+    input::keyboard::{Key, KeyboardInput},
+    prelude::*,
+};
+
+use super::Screen;
+use crate::{
+    game::{
+        assets::{HandleMap, LevelKey},
+        campaign::{Campaign, CurrentLevel, CAMPAIGN},
+        generator::daily_seed,
+        level::Level,
+    },
+    ui::prelude::*,
+};
+
+pub(super) fn plugin(app: &mut App) {
+    app.add_systems(OnEnter(Screen::LevelSelect), enter_level_select);
+
+    app.register_type::<LevelSelectAction>();
+    app.init_resource::<SeedInput>();
+    app.add_systems(
+        Update,
+        (handle_level_select_action, type_seed).run_if(in_state(Screen::LevelSelect)),
+    );
+}
+
+/// Digits typed on this screen, to play a seed someone shared.
+#[derive(Resource, Debug, Default)]
+struct SeedInput(String);
+
+#[derive(Component)]
+struct SeedLabel;
+
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Reflect)]
+#[reflect(Component)]
+enum LevelSelectAction {
+    Play(usize),
+    Daily,
+    Seed,
+    RandomSeed,
+    Back,
+}
+
+fn enter_level_select(
+    mut commands: Commands,
+    campaign: Res<Campaign>,
+    level_handles: Res<HandleMap<LevelKey>>,
+    levels: Res<Assets<Level>>,
+    seed_input: Res<SeedInput>,
+) {
+    commands
+        .ui_root()
+        .insert(StateScoped(Screen::LevelSelect))
+        .with_children(|children| {
+            children.header("Levels");
+            for (index, key) in CAMPAIGN.iter().enumerate() {
+                let name = levels
+                    .get(&level_handles[key])
+                    .map_or("???", |level| level.name.as_str());
+                if campaign.is_unlocked(index) {
+                    children
+                        .button(format!("{}. {name}", index + 1))
+                        .insert(LevelSelectAction::Play(index));
+                } else {
+                    children.label(format!("{}. locked", index + 1));
+                }
+            }
+
+            children.header("Generated");
+            children
+                .button(format!("Daily #{}", daily_seed()))
+                .insert(LevelSelectAction::Daily);
+            children.label(seed_label(&seed_input)).insert(SeedLabel);
+            children.button("Play seed").insert(LevelSelectAction::Seed);
+            children.button("Random").insert(LevelSelectAction::RandomSeed);
+            children.button("Back").insert(LevelSelectAction::Back);
+        });
+}
+
+fn handle_level_select_action(
+    mut next_screen: ResMut<NextState<Screen>>,
+    mut button_query: InteractionQuery<&LevelSelectAction>,
+    mut current_level: ResMut<CurrentLevel>,
+    level_handles: Res<HandleMap<LevelKey>>,
+    mut levels: ResMut<Assets<Level>>,
+    seed_input: Res<SeedInput>,
+) {
+    for (interaction, action) in &mut button_query {
+        if matches!(interaction, Interaction::Pressed) {
+            match action {
+                LevelSelectAction::Play(index) => {
+                    *current_level = CurrentLevel::campaign(*index, &level_handles);
+                    next_screen.set(Screen::Playing);
+                }
+                LevelSelectAction::Daily => {
+                    *current_level = CurrentLevel::generated(daily_seed(), &mut levels);
+                    next_screen.set(Screen::Playing);
+                }
+                LevelSelectAction::Seed => {
+                    let Ok(seed) = seed_input.0.parse() else {
+                        continue;
+                    };
+                    *current_level = CurrentLevel::generated(seed, &mut levels);
+                    next_screen.set(Screen::Playing);
+                }
+                LevelSelectAction::RandomSeed => {
+                    *current_level = CurrentLevel::generated(rand::random(), &mut levels);
+                    next_screen.set(Screen::Playing);
+                }
+                LevelSelectAction::Back => next_screen.set(Screen::Title),
+            }
+        }
+    }
+}
+
+fn seed_label(seed_input: &SeedInput) -> String {
+    match seed_input.0.is_empty() {
+        true => "type a seed".to_string(),
+        false => format!("seed: {}", seed_input.0),
+    }
+}
+
+fn type_seed(
+    mut keys: EventReader<KeyboardInput>,
+    mut seed_input: ResMut<SeedInput>,
+    labels: Query<&Children, With<SeedLabel>>,
+    mut texts: Query<&mut Text>,
+) {
+    for key in keys.read().filter(|key| key.state.is_pressed()) {
+        match &key.logical_key {
+            Key::Character(character) if character.chars().all(|c| c.is_ascii_digit()) => {
+                // Anything longer would not fit in a `u64`.
+                if seed_input.0.len() + character.len() <= 19 {
+                    seed_input.0.push_str(character);
+                }
+            }
+            Key::Backspace => {
+                seed_input.0.pop();
+            }
+            _ => continue,
+        }
+        for children in &labels {
+            let mut texts = texts.iter_many_mut(children);
+            while let Some(mut text) = texts.fetch_next() {
+                text.sections[0].value = seed_label(&seed_input);
+            }
+        }
+    }
+}
@@ -5,7 +5,10 @@ use bevy::prelude::*;
 
 use super::Screen;
 use crate::{
-    game::assets::{HandleMap, ImageKey, SfxKey, SoundtrackKey},
+    game::{
+        assets::{HandleMap, ImageKey, LevelKey, SfxKey, SoundtrackKey},
+        save::PendingLoad,
+    },
     ui::prelude::*,
 };
 
@@ -13,7 +16,7 @@ pub(super) fn plugin(app: &mut App) {
     app.add_systems(OnEnter(Screen::Loading), enter_loading);
     app.add_systems(
         Update,
-        continue_to_title.run_if(in_state(Screen::Loading).and_then(all_assets_loaded)),
+        continue_from_loading.run_if(in_state(Screen::Loading).and_then(all_assets_loaded)),
     );
 }
 
@@ -31,12 +34,22 @@ fn all_assets_loaded(
     image_handles: Res<HandleMap<ImageKey>>,
     sfx_handles: Res<HandleMap<SfxKey>>,
     soundtrack_handles: Res<HandleMap<SoundtrackKey>>,
+    level_handles: Res<HandleMap<LevelKey>>,
 ) -> bool {
     image_handles.all_loaded(&asset_server)
         && sfx_handles.all_loaded(&asset_server)
         && soundtrack_handles.all_loaded(&asset_server)
+        && level_handles.all_loaded(&asset_server)
 }
 
-fn continue_to_title(mut next_screen: ResMut<NextState<Screen>>) {
-    next_screen.set(Screen::Title);
+/// Loading a saved run also comes through here, and goes straight back to playing.
+fn continue_from_loading(
+    pending_load: Option<Res<PendingLoad>>,
+    mut next_screen: ResMut<NextState<Screen>>,
+) {
+    next_screen.set(if pending_load.is_some() {
+        Screen::Playing
+    } else {
+        Screen::Title
+    });
 }
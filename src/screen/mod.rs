@@ -1,9 +1,12 @@
 //! The game's main screen states and transitions between them.
 
 mod credits;
+mod editor;
 mod loading;
 mod hell;
+mod level_select;
 mod playing;
+mod replay;
 mod splash;
 mod title;
 mod win;
@@ -13,13 +16,18 @@ use bevy::prelude::*;
 pub(super) fn plugin(app: &mut App) {
     app.init_state::<Screen>();
     app.enable_state_scoped_entities::<Screen>();
+    app.add_computed_state::<InGame>();
+    app.enable_state_scoped_entities::<InGame>();
 
     app.add_plugins((
         splash::plugin,
         loading::plugin,
         title::plugin,
+        level_select::plugin,
         credits::plugin,
         playing::plugin,
+        replay::plugin,
+        editor::plugin,
         hell::plugin,
         win::plugin,
     ));
@@ -32,8 +40,25 @@ pub enum Screen {
     Splash,
     Loading,
     Title,
+    LevelSelect,
     Credits,
     Playing,
+    Editor,
     Hell,
-    Win
+    Win,
+    /// Watching a recorded run.
+    Replay,
+}
+
+/// Whether a stage is up, either being played or replayed.
+/// Gameplay runs in both, only input and what ends the run differ.
+#[derive(Debug, Hash, PartialEq, Eq, Clone)]
+pub struct InGame;
+
+impl ComputedStates for InGame {
+    type SourceStates = Screen;
+
+    fn compute(screen: Screen) -> Option<Self> {
+        matches!(screen, Screen::Playing | Screen::Replay).then_some(InGame)
+    }
 }
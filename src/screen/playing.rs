@@ -1,21 +1,22 @@
 //! The screen state for the main game loop.
 
-use bevy::{input::common_conditions::input_just_pressed, prelude::*};
+mod hud;
+mod pause;
+
+use bevy::prelude::*;
 
 use super::Screen;
 use crate::game::{
-    assets::SoundtrackKey, audio::soundtrack::PlaySoundtrack, spawn::level::SpawnLevel,
+    assets::SoundtrackKey, audio::soundtrack::PlaySoundtrack, movement::RunOver,
+    spawn::level::SpawnLevel,
 };
 
 pub(super) fn plugin(app: &mut App) {
+    app.add_plugins((hud::plugin, pause::plugin));
+
     app.add_systems(OnEnter(Screen::Playing), enter_playing);
     app.add_systems(OnExit(Screen::Playing), exit_playing);
-
-    app.add_systems(
-        Update,
-        return_to_title_screen
-            .run_if(in_state(Screen::Playing).and_then(input_just_pressed(KeyCode::Escape))),
-    );
+    app.observe(end_run);
 }
 
 fn enter_playing(mut commands: Commands) {
@@ -28,6 +29,17 @@ fn exit_playing(mut commands: Commands) {
     commands.trigger(PlaySoundtrack::Disable);
 }
 
-fn return_to_title_screen(mut next_screen: ResMut<NextState<Screen>>) {
-    next_screen.set(Screen::Title);
+fn end_run(
+    trigger: Trigger<RunOver>,
+    screen: Res<State<Screen>>,
+    mut next_screen: ResMut<NextState<Screen>>,
+) {
+    // Replays play on to the end of their input instead.
+    if *screen.get() != Screen::Playing {
+        return;
+    }
+    next_screen.set(match trigger.event() {
+        RunOver::Lost => Screen::Hell,
+        RunOver::Won => Screen::Win,
+    });
 }
@@ -0,0 +1,384 @@
+//! The timeline shown while playing.
+//! One lane shows how far into the loop we are, then every ghost gets a lane
+//! with what it did in its loop, and every guard one with its waypoints.
+
+use bevy::{prelude::*, ui::Val::*};
+
+use crate::{
+    game::{
+        assets::NlaTrack,
+        ghosts::Locked,
+        guards::Alarm,
+        movement::{seconds_to_ticks, Ghost, IsDead, Npc, Path, Timeloop},
+        paradox::KilledBy,
+        recording::GhostRecording,
+        rewind::Rewind,
+        spatial::WallIndex,
+        spawn::{player::Player, stage::Furnace},
+        vision::{exposure, lights},
+    },
+    screen::{InGame, Screen},
+    ui::prelude::*,
+};
+
+pub(super) fn plugin(app: &mut App) {
+    app.add_systems(OnEnter(InGame), spawn_hud);
+    app.add_systems(
+        Update,
+        (update_hud_status, update_hud_cursor, rebuild_hud_lanes)
+            .run_if(in_state(InGame)),
+    );
+}
+
+/// Warn this long before the loop resets and a new ghost appears.
+const WARNING_SECONDS: f32 = 3.0;
+const LANE_HEIGHT: f32 = 10.0;
+
+#[derive(Component)]
+struct HudStatus;
+
+#[derive(Component)]
+struct HudLabels;
+
+#[derive(Component)]
+struct HudBars;
+
+#[derive(Component)]
+struct HudCursor;
+
+#[derive(Component)]
+struct HudProgress;
+
+/// Everything that gets rebuilt when guards die or ghosts appear.
+#[derive(Component)]
+struct HudLane;
+
+fn spawn_hud(mut commands: Commands) {
+    commands
+        .spawn((
+            Name::new("HUD"),
+            NodeBundle {
+                style: Style {
+                    width: Percent(100.0),
+                    height: Percent(100.0),
+                    justify_content: JustifyContent::End,
+                    align_items: AlignItems::Center,
+                    flex_direction: FlexDirection::Column,
+                    padding: UiRect::all(Px(10.0)),
+                    row_gap: Px(4.0),
+                    position_type: PositionType::Absolute,
+                    ..default()
+                },
+                ..default()
+            },
+            StateScoped(InGame),
+        ))
+        .with_children(|children| {
+            children.spawn((
+                Name::new("HUD Status"),
+                TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font_size: 20.0,
+                        color: ui_palette::LABEL_TEXT,
+                        ..default()
+                    },
+                ),
+                HudStatus,
+            ));
+            children
+                .spawn((
+                    Name::new("HUD Timeline"),
+                    NodeBundle {
+                        style: Style {
+                            width: Percent(80.0),
+                            ..default()
+                        },
+                        ..default()
+                    },
+                ))
+                .with_children(|children| {
+                    children.spawn((
+                        Name::new("HUD Labels"),
+                        NodeBundle {
+                            style: Style {
+                                width: Px(140.0),
+                                flex_direction: FlexDirection::Column,
+                                row_gap: Px(2.0),
+                                ..default()
+                            },
+                            ..default()
+                        },
+                        HudLabels,
+                    ));
+                    children
+                        .spawn((
+                            Name::new("HUD Bars"),
+                            NodeBundle {
+                                style: Style {
+                                    flex_grow: 1.0,
+                                    flex_direction: FlexDirection::Column,
+                                    row_gap: Px(2.0),
+                                    ..default()
+                                },
+                                ..default()
+                            },
+                            HudBars,
+                        ))
+                        .with_children(|children| {
+                            children.spawn((
+                                Name::new("HUD Cursor"),
+                                NodeBundle {
+                                    style: Style {
+                                        position_type: PositionType::Absolute,
+                                        width: Px(3.0),
+                                        height: Percent(100.0),
+                                        ..default()
+                                    },
+                                    background_color: BackgroundColor(ui_palette::LABEL_TEXT),
+                                    z_index: ZIndex::Local(1),
+                                    ..default()
+                                },
+                                HudCursor,
+                            ));
+                        });
+                });
+        });
+}
+
+fn update_hud_status(
+    screen: Res<State<Screen>>,
+    timeloop: Res<Timeloop>,
+    rewind: Res<Rewind>,
+    alarm: Res<Alarm>,
+    players: Query<(Entity, &Transform), With<Player>>,
+    furnaces: Query<(&Transform, &Furnace)>,
+    walls: Res<WallIndex>,
+    mut status: Query<&mut Text, With<HudStatus>>,
+) {
+    let remaining = timeloop.max_time() - timeloop.current_time();
+    // How easy the player is to spot where they stand.
+    let lights = lights(furnaces.iter(), players.iter());
+    let light = players
+        .get_single()
+        .map(|(player, transform)| exposure(transform.translation, Some(player), &lights, &walls))
+        .unwrap_or_default();
+    for mut text in &mut status {
+        let section = &mut text.sections[0];
+        section.value = format!(
+            "Loop {} | {:.1} / {:.1} s | {} | light {:.0}%{}{}",
+            timeloop.gen + 1,
+            timeloop.current_time(),
+            timeloop.max_time(),
+            if rewind.held {
+                "rewinding".to_string()
+            } else {
+                format!("new ghost in {remaining:.1} s")
+            },
+            light * 100.0,
+            if alarm.raised { " | ALARM" } else { "" },
+            if *screen.get() == Screen::Playing {
+                " | Shift sprint | Space throw | hold R to rewind | Esc pause"
+            } else {
+                ""
+            },
+        );
+        section.style.color = if remaining < WARNING_SECONDS || alarm.raised {
+            ui_palette::BUTTON_TEXT
+        } else {
+            ui_palette::LABEL_TEXT
+        };
+    }
+}
+
+fn update_hud_cursor(
+    timeloop: Res<Timeloop>,
+    mut cursor: Query<&mut Style, (With<HudCursor>, Without<HudProgress>)>,
+    mut progress: Query<&mut Style, (With<HudProgress>, Without<HudCursor>)>,
+) {
+    let position = fraction(timeloop.tick, timeloop.max_ticks);
+    for mut style in &mut cursor {
+        style.left = position;
+    }
+    for mut style in &mut progress {
+        style.width = position;
+    }
+}
+
+fn rebuild_hud_lanes(
+    mut commands: Commands,
+    timeloop: Res<Timeloop>,
+    recording: Res<GhostRecording>,
+    guards: Query<(&Name, &Path, Option<&KilledBy>, Has<IsDead>), With<Npc>>,
+    ghosts: Query<(&Ghost, Has<IsDead>, Has<Locked>)>,
+    changed: Query<
+        (),
+        Or<(
+            Added<Path>,
+            Added<Ghost>,
+            Added<IsDead>,
+            Added<KilledBy>,
+            Added<Locked>,
+        )>,
+    >,
+    mut removed_ghosts: RemovedComponents<Ghost>,
+    mut removed_deaths: RemovedComponents<IsDead>,
+    mut removed_locks: RemovedComponents<Locked>,
+    labels: Query<Entity, With<HudLabels>>,
+    bars: Query<Entity, With<HudBars>>,
+    lanes: Query<Entity, With<HudLane>>,
+) {
+    let removed = removed_ghosts.read().count()
+        + removed_deaths.read().count()
+        + removed_locks.read().count()
+        > 0;
+    if changed.is_empty() && !removed {
+        return;
+    }
+    let (Ok(labels), Ok(bars)) = (labels.get_single(), bars.get_single()) else {
+        return;
+    };
+    for lane in &lanes {
+        commands.entity(lane).despawn_recursive();
+    }
+
+    let max_ticks = timeloop.max_ticks;
+    let mut add_lane = |label: String, dim: bool, markers: Vec<Marker>| {
+        commands.entity(labels).with_children(|children| {
+            children.spawn((
+                Name::new("HUD Lane Label"),
+                TextBundle::from_section(
+                    label,
+                    TextStyle {
+                        font_size: LANE_HEIGHT + 2.0,
+                        color: if dim {
+                            ui_palette::LABEL_TEXT.with_alpha(0.4)
+                        } else {
+                            ui_palette::LABEL_TEXT
+                        },
+                        ..default()
+                    },
+                )
+                .with_style(Style {
+                    height: Px(LANE_HEIGHT),
+                    ..default()
+                }),
+                HudLane,
+            ));
+        });
+        commands.entity(bars).with_children(|children| {
+            children
+                .spawn((
+                    Name::new("HUD Lane"),
+                    NodeBundle {
+                        style: Style {
+                            height: Px(LANE_HEIGHT),
+                            ..default()
+                        },
+                        background_color: BackgroundColor(ui_palette::NODE_BACKGROUND),
+                        ..default()
+                    },
+                    HudLane,
+                ))
+                .with_children(|children| {
+                    for marker in markers {
+                        let mut entity = children.spawn(NodeBundle {
+                            style: Style {
+                                position_type: PositionType::Absolute,
+                                left: fraction(marker.start, max_ticks),
+                                width: marker.end.map_or(Px(4.0), |end| {
+                                    fraction(end.saturating_sub(marker.start), max_ticks)
+                                }),
+                                height: Percent(100.0),
+                                ..default()
+                            },
+                            background_color: BackgroundColor(marker.color),
+                            ..default()
+                        });
+                        if marker.progress {
+                            entity.insert(HudProgress);
+                        }
+                    }
+                });
+        });
+    };
+
+    add_lane(
+        "Now".to_string(),
+        false,
+        vec![Marker {
+            start: 0,
+            end: Some(timeloop.tick),
+            color: ui_palette::BUTTON_HOVERED_BACKGROUND,
+            progress: true,
+        }],
+    );
+
+    let mut ghosts: Vec<_> = ghosts.iter().collect();
+    ghosts.sort_by_key(|(ghost, ..)| ghost.gen);
+    for (ghost, is_dead, locked) in ghosts {
+        let mut markers = vec![];
+        if let Some(recorded) = recording.loops.get(usize::from(ghost.gen)) {
+            let runs = &recorded.runs;
+            for (index, (start, sample)) in runs.iter().enumerate() {
+                if sample.track != NlaTrack::Walk {
+                    continue;
+                }
+                let end = runs.get(index + 1).map_or(recorded.ticks, |(next, _)| *next);
+                markers.push(Marker {
+                    start: *start,
+                    end: Some(end),
+                    color: ui_palette::LABEL_TEXT.with_alpha(0.5),
+                    progress: false,
+                });
+            }
+        }
+        for (_, _, killed_by, _) in &guards {
+            if let Some(killed_by) = killed_by.filter(|killed_by| killed_by.gen == ghost.gen) {
+                markers.push(Marker::at(killed_by.tick, ui_palette::BUTTON_TEXT));
+            }
+        }
+        let label = format!(
+            "Ghost of loop {}{}",
+            ghost.gen + 1,
+            if locked { " (locked)" } else { "" }
+        );
+        add_lane(label, is_dead, markers);
+    }
+
+    for (name, path, killed_by, is_dead) in &guards {
+        let mut markers: Vec<Marker> = path
+            .points
+            .iter()
+            .map(|(time, _)| Marker::at(seconds_to_ticks(*time), ui_palette::LABEL_TEXT))
+            .collect();
+        if let Some(killed_by) = killed_by {
+            markers.push(Marker::at(killed_by.tick, ui_palette::BUTTON_TEXT));
+        }
+        add_lane(name.to_string(), is_dead, markers);
+    }
+}
+
+/// Something on a lane, either a point in time or a stretch of it.
+struct Marker {
+    start: u32,
+    end: Option<u32>,
+    color: Color,
+    /// Whether this is the bar that fills up as the loop goes on.
+    progress: bool,
+}
+
+impl Marker {
+    fn at(tick: u32, color: Color) -> Self {
+        Self {
+            start: tick,
+            end: None,
+            color,
+            progress: false,
+        }
+    }
+}
+
+fn fraction(ticks: u32, max_ticks: u32) -> Val {
+    Percent(100.0 * ticks as f32 / max_ticks.max(1) as f32)
+}
@@ -0,0 +1,179 @@
+//! The menu that opens over a run when Escape is pressed.
+//! Gameplay runs on virtual time, so pausing it stops every tick until the
+//! menu is closed again. Ghosts are managed from here too, one row per
+//! generation with buttons to lock or erase it.
+
+use bevy::{input::common_conditions::input_just_pressed, prelude::*, ui::Val::*};
+
+use crate::{
+    game::{
+        ghosts::{GhostOrder, Locked},
+        movement::Ghost,
+        save::{LoadRun, SaveRun},
+    },
+    screen::Screen,
+    ui::prelude::*,
+};
+
+pub(super) fn plugin(app: &mut App) {
+    app.add_sub_state::<Pause>();
+    app.enable_state_scoped_entities::<Pause>();
+
+    app.add_systems(OnEnter(Pause::Paused), enter_pause);
+    app.add_systems(OnExit(Pause::Paused), exit_pause);
+
+    app.register_type::<PauseAction>();
+    app.add_systems(
+        Update,
+        (
+            toggle_pause.run_if(input_just_pressed(KeyCode::Escape)),
+            (handle_pause_action, rebuild_ghost_list).run_if(in_state(Pause::Paused)),
+        )
+            .run_if(in_state(Screen::Playing)),
+    );
+}
+
+/// Whether the run is paused, only exists while [`Screen::Playing`].
+#[derive(SubStates, Debug, Hash, PartialEq, Eq, Clone, Default)]
+#[source(Screen = Screen::Playing)]
+pub enum Pause {
+    #[default]
+    Running,
+    Paused,
+}
+
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Reflect)]
+#[reflect(Component)]
+enum PauseAction {
+    Resume,
+    Save,
+    Load,
+    Title,
+    ToggleLock(u16),
+    Erase(u16),
+}
+
+#[derive(Component)]
+struct GhostList;
+
+/// One generation in the [`GhostList`].
+#[derive(Component)]
+struct GhostRow;
+
+fn enter_pause(mut commands: Commands, mut time: ResMut<Time<Virtual>>) {
+    time.pause();
+    commands
+        .ui_root()
+        .insert(StateScoped(Pause::Paused))
+        .with_children(|children| {
+            children.header("Paused");
+            children.spawn(row()).with_children(|children| {
+                children.button("Resume").insert(PauseAction::Resume);
+                children.button("Save").insert(PauseAction::Save);
+                children.button("Load").insert(PauseAction::Load);
+                children.button("Title").insert(PauseAction::Title);
+            });
+            children.spawn((
+                Name::new("Ghost List"),
+                NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Column,
+                        row_gap: Px(10.0),
+                        ..default()
+                    },
+                    ..default()
+                },
+                GhostList,
+            ));
+        });
+}
+
+fn exit_pause(mut time: ResMut<Time<Virtual>>) {
+    time.unpause();
+}
+
+fn toggle_pause(pause: Res<State<Pause>>, mut next_pause: ResMut<NextState<Pause>>) {
+    next_pause.set(match pause.get() {
+        Pause::Running => Pause::Paused,
+        Pause::Paused => Pause::Running,
+    });
+}
+
+fn handle_pause_action(
+    mut commands: Commands,
+    mut next_pause: ResMut<NextState<Pause>>,
+    mut next_screen: ResMut<NextState<Screen>>,
+    mut button_query: InteractionQuery<&PauseAction>,
+) {
+    for (interaction, action) in &mut button_query {
+        if matches!(interaction, Interaction::Pressed) {
+            match action {
+                PauseAction::Resume => next_pause.set(Pause::Running),
+                PauseAction::Save => {
+                    commands.trigger(SaveRun);
+                    next_pause.set(Pause::Running);
+                }
+                PauseAction::Load => commands.trigger(LoadRun),
+                PauseAction::Title => next_screen.set(Screen::Title),
+                PauseAction::ToggleLock(gen) => {
+                    commands.trigger(GhostOrder::ToggleLock { gen: *gen });
+                }
+                PauseAction::Erase(gen) => commands.trigger(GhostOrder::Erase { gen: *gen }),
+            }
+        }
+    }
+}
+
+fn row() -> NodeBundle {
+    NodeBundle {
+        style: Style {
+            column_gap: Px(10.0),
+            align_items: AlignItems::Center,
+            ..default()
+        },
+        ..default()
+    }
+}
+
+fn rebuild_ghost_list(
+    mut commands: Commands,
+    ghosts: Query<(&Ghost, Has<Locked>)>,
+    changed: Query<(), Or<(Added<GhostList>, Added<Ghost>, Added<Locked>)>>,
+    mut removed_ghosts: RemovedComponents<Ghost>,
+    mut removed_locks: RemovedComponents<Locked>,
+    list: Query<Entity, With<GhostList>>,
+    rows: Query<Entity, With<GhostRow>>,
+) {
+    let removed = removed_ghosts.read().count() + removed_locks.read().count() > 0;
+    if changed.is_empty() && !removed {
+        return;
+    }
+    let Ok(list) = list.get_single() else {
+        return;
+    };
+    for row in &rows {
+        commands.entity(row).despawn_recursive();
+    }
+
+    let mut ghosts: Vec<_> = ghosts.iter().collect();
+    ghosts.sort_by_key(|(ghost, _)| ghost.gen);
+    commands.entity(list).with_children(|children| {
+        for (ghost, locked) in ghosts {
+            children
+                .spawn((row(), GhostRow))
+                .with_children(|children| {
+                    children.label(format!(
+                        "Ghost of loop {}{}",
+                        ghost.gen + 1,
+                        if locked { " (locked)" } else { "" }
+                    ));
+                    children
+                        .button(if locked { "Unlock" } else { "Lock" })
+                        .insert(PauseAction::ToggleLock(ghost.gen));
+                    children
+                        .button("Erase")
+                        .insert(PauseAction::Erase(ghost.gen));
+                });
+        }
+    });
+}
@@ -0,0 +1,174 @@
+//! The screen for watching a recorded run.
+//! The stage is spawned and simulated exactly like when playing, the
+//! [`ReplayPlayer`] just stands in for the keyboard. Time can be paused and
+//! sped up, and the camera can be let go of the player to look around.
+
+use bevy::{prelude::*, ui::Val::*};
+
+use super::Screen;
+use crate::{
+    game::{
+        assets::SoundtrackKey,
+        audio::soundtrack::PlaySoundtrack,
+        movement::follow_player,
+        replay::ReplayPlayer,
+        spawn::level::SpawnLevel,
+    },
+    ui::prelude::*,
+    AppSet,
+};
+
+pub(super) fn plugin(app: &mut App) {
+    app.init_resource::<ReplayCamera>();
+
+    app.add_systems(OnEnter(Screen::Replay), enter_replay);
+    app.add_systems(OnExit(Screen::Replay), exit_replay);
+    app.add_systems(
+        Update,
+        (
+            handle_replay_input.in_set(AppSet::RecordInput),
+            (update_replay_status, move_free_camera.after(follow_player)).in_set(AppSet::Update),
+        )
+            .run_if(in_state(Screen::Replay)),
+    );
+}
+
+const MIN_SPEED: f32 = 0.25;
+const MAX_SPEED: f32 = 8.0;
+/// How fast the free camera moves, in units per second.
+const FREE_CAMERA_SPEED: f32 = 20.0;
+
+#[derive(Resource, Debug, Default)]
+struct ReplayCamera {
+    /// Where the camera is while it doesn't follow the player.
+    free: Option<Vec3>,
+}
+
+#[derive(Component)]
+struct ReplayStatus;
+
+fn enter_replay(mut commands: Commands, mut camera: ResMut<ReplayCamera>) {
+    camera.free = None;
+    commands.trigger(SpawnLevel);
+    commands.trigger(PlaySoundtrack::Key(SoundtrackKey::Gameplay));
+    commands.spawn((
+        Name::new("Replay Status"),
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font_size: 20.0,
+                color: ui_palette::LABEL_TEXT,
+                ..default()
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            top: Px(10.0),
+            left: Px(10.0),
+            ..default()
+        }),
+        ReplayStatus,
+        StateScoped(Screen::Replay),
+    ));
+}
+
+fn exit_replay(mut commands: Commands, mut time: ResMut<Time<Virtual>>) {
+    time.unpause();
+    time.set_relative_speed(1.0);
+    commands.remove_resource::<ReplayPlayer>();
+    commands.trigger(PlaySoundtrack::Disable);
+}
+
+fn handle_replay_input(
+    input: Res<ButtonInput<KeyCode>>,
+    mut time: ResMut<Time<Virtual>>,
+    mut camera: ResMut<ReplayCamera>,
+    camera_transform: Query<&Transform, With<Camera3d>>,
+    mut next_screen: ResMut<NextState<Screen>>,
+) {
+    if input.just_pressed(KeyCode::Escape) {
+        next_screen.set(Screen::Title);
+    }
+    if input.just_pressed(KeyCode::Space) {
+        if time.is_paused() {
+            time.unpause();
+        } else {
+            time.pause();
+        }
+    }
+    let speed = time.relative_speed();
+    if input.just_pressed(KeyCode::Minus) {
+        time.set_relative_speed((speed / 2.0).max(MIN_SPEED));
+    }
+    if input.just_pressed(KeyCode::Equal) {
+        time.set_relative_speed((speed * 2.0).min(MAX_SPEED));
+    }
+    if input.just_pressed(KeyCode::KeyF) {
+        camera.free = match camera.free {
+            Some(_) => None,
+            None => camera_transform
+                .get_single()
+                .ok()
+                .map(|transform| transform.translation),
+        };
+    }
+}
+
+/// Fly the camera around with the movement keys, on real time so it works while paused.
+fn move_free_camera(
+    input: Res<ButtonInput<KeyCode>>,
+    time: Res<Time<Real>>,
+    mut camera: ResMut<ReplayCamera>,
+    mut camera_transform: Query<&mut Transform, With<Camera3d>>,
+) {
+    let Some(position) = camera.free.as_mut() else {
+        return;
+    };
+    // Same directions as the player's controls, see `record_movement_controller`.
+    let mut direction = Vec3::ZERO;
+    if input.pressed(KeyCode::KeyW) || input.pressed(KeyCode::ArrowUp) {
+        direction.z += 1.0;
+    }
+    if input.pressed(KeyCode::KeyS) || input.pressed(KeyCode::ArrowDown) {
+        direction.z -= 1.0;
+    }
+    if input.pressed(KeyCode::KeyA) || input.pressed(KeyCode::ArrowLeft) {
+        direction.x += 1.0;
+    }
+    if input.pressed(KeyCode::KeyD) || input.pressed(KeyCode::ArrowRight) {
+        direction.x -= 1.0;
+    }
+    *position += direction.normalize_or_zero() * FREE_CAMERA_SPEED * time.delta_seconds();
+    for mut transform in &mut camera_transform {
+        transform.translation = *position;
+    }
+}
+
+fn update_replay_status(
+    replay_player: Option<Res<ReplayPlayer>>,
+    time: Res<Time<Virtual>>,
+    camera: Res<ReplayCamera>,
+    mut status: Query<&mut Text, With<ReplayStatus>>,
+) {
+    let Some(replay_player) = replay_player else {
+        return;
+    };
+    let state = if replay_player.is_finished() {
+        "finished"
+    } else if time.is_paused() {
+        "paused"
+    } else {
+        "playing"
+    };
+    for mut text in &mut status {
+        text.sections[0].value = format!(
+            "Replay {} | tick {} / {} | x{} | {} camera\n\
+             Space pause  -/= speed  F free camera  Esc back",
+            state,
+            replay_player.tick,
+            replay_player.replay.ticks,
+            time.relative_speed(),
+            if camera.free.is_some() { "free" } else { "following" },
+        );
+    }
+}
@@ -3,7 +3,7 @@
 use bevy::prelude::*;
 
 use super::Screen;
-use crate::ui::prelude::*;
+use crate::{game::replay::WatchReplay, ui::prelude::*};
 
 pub(super) fn plugin(app: &mut App) {
     app.add_systems(OnEnter(Screen::Title), enter_title);
@@ -16,6 +16,10 @@ pub(super) fn plugin(app: &mut App) {
 #[reflect(Component)]
 enum TitleAction {
     Play,
+    LevelSelect,
+    /// Watch the last run again.
+    Replay,
+    Editor,
     Credits,
     /// Exit doesn't work well with embedded applications.
     #[cfg(not(target_family = "wasm"))]
@@ -28,6 +32,9 @@ fn enter_title(mut commands: Commands) {
         .insert(StateScoped(Screen::Title))
         .with_children(|children| {
             children.button("kill everyone( not you ))").insert(TitleAction::Play);
+            children.button("Levels").insert(TitleAction::LevelSelect);
+            children.button("Replay").insert(TitleAction::Replay);
+            children.button("Editor").insert(TitleAction::Editor);
             children.button("Credits").insert(TitleAction::Credits);
 
             #[cfg(not(target_family = "wasm"))]
@@ -36,6 +43,7 @@ fn enter_title(mut commands: Commands) {
 }
 
 fn handle_title_action(
+    mut commands: Commands,
     mut next_screen: ResMut<NextState<Screen>>,
     mut button_query: InteractionQuery<&TitleAction>,
     #[cfg(not(target_family = "wasm"))] mut app_exit: EventWriter<AppExit>,
@@ -44,6 +52,9 @@ fn handle_title_action(
         if matches!(interaction, Interaction::Pressed) {
             match action {
                 TitleAction::Play => next_screen.set(Screen::Playing),
+                TitleAction::LevelSelect => next_screen.set(Screen::LevelSelect),
+                TitleAction::Replay => commands.trigger(WatchReplay),
+                TitleAction::Editor => next_screen.set(Screen::Editor),
                 TitleAction::Credits => next_screen.set(Screen::Credits),
 
                 #[cfg(not(target_family = "wasm"))]
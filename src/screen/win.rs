@@ -5,7 +5,8 @@ use bevy::prelude::*;
 use super::Screen;
 use crate::{
     game::{
-        assets::SoundtrackKey, audio::soundtrack::PlaySoundtrack,
+        assets::{HandleMap, LevelKey, SoundtrackKey}, audio::soundtrack::PlaySoundtrack,
+        campaign::CurrentLevel,
     },
     ui::prelude::*
 };
@@ -23,16 +24,22 @@ pub(super) fn plugin(app: &mut App) {
 #[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Reflect)]
 #[reflect(Component)]
 enum WinAction {
+    Next(usize),
+    LevelSelect,
     Back,
 }
 
-fn enter_win(mut commands: Commands) {
+fn enter_win(mut commands: Commands, current_level: Res<CurrentLevel>) {
     commands
         .ui_root()
         .insert(StateScoped(Screen::Win))
         .with_children(|children| {
             children.label(" you won and went to heaven and you are happy and everyone here loves you <3 <# <3");
 
+            if let Some(next) = current_level.next() {
+                children.button("next level").insert(WinAction::Next(next));
+            }
+            children.button("levels").insert(WinAction::LevelSelect);
 
             children.button("escape").insert(WinAction::Back);
         });    
@@ -46,10 +53,17 @@ fn exit_win(mut commands: Commands) {
 fn handle_win_action(
     mut next_screen: ResMut<NextState<Screen>>,
     mut button_query: InteractionQuery<&WinAction>,
+    mut current_level: ResMut<CurrentLevel>,
+    level_handles: Res<HandleMap<LevelKey>>,
 ) {
     for (interaction, action) in &mut button_query {
         if matches!(interaction, Interaction::Pressed) {
             match action {
+                WinAction::Next(index) => {
+                    *current_level = CurrentLevel::campaign(*index, &level_handles);
+                    next_screen.set(Screen::Playing);
+                }
+                WinAction::LevelSelect => next_screen.set(Screen::LevelSelect),
                 WinAction::Back => next_screen.set(Screen::Title),
             }
         }